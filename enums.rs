@@ -1,8 +0,0 @@
-// Enum definition for PostStatus
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum PostStatus {
-    DRAFT,
-    PUBLISHED,
-    ARCHIVED,
-}
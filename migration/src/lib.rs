@@ -1,12 +1,38 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_create_table;
+mod m20241123_000001_add_user_password_hash;
+mod m20241123_000002_create_refresh_token;
+mod m20241123_000003_create_user_token;
+mod m20241123_000004_add_two_factor;
+mod m20241123_000005_add_user_lockout;
+mod m20241123_000006_add_post_search;
+mod m20241123_000007_create_category;
+mod m20241123_000008_create_comment;
+mod m20241123_000009_add_comment_replies;
+mod m20241123_000010_add_post_status;
+mod m20241123_000011_add_post_schedule;
+mod m20241123_000012_unique_profile_user;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20220101_000001_create_table::Migration),
+            Box::new(m20241123_000001_add_user_password_hash::Migration),
+            Box::new(m20241123_000002_create_refresh_token::Migration),
+            Box::new(m20241123_000003_create_user_token::Migration),
+            Box::new(m20241123_000004_add_two_factor::Migration),
+            Box::new(m20241123_000005_add_user_lockout::Migration),
+            Box::new(m20241123_000006_add_post_search::Migration),
+            Box::new(m20241123_000007_create_category::Migration),
+            Box::new(m20241123_000008_create_comment::Migration),
+            Box::new(m20241123_000009_add_comment_replies::Migration),
+            Box::new(m20241123_000010_add_post_status::Migration),
+            Box::new(m20241123_000011_add_post_schedule::Migration),
+            Box::new(m20241123_000012_unique_profile_user::Migration),
+        ]
     }
 }
@@ -149,6 +149,7 @@ impl MigrationTrait for Migration {
 }
 
 #[derive(Iden)]
+#[allow(clippy::enum_variant_names)]
 pub enum User {
     Table,
     Id,
@@ -0,0 +1,129 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(ColumnDef::new(User::VerifiedAt).timestamp_with_time_zone())
+                    .to_owned(),
+            )
+            .await?;
+
+        let create_enum = Statement::from_string(
+            manager.get_database_backend(),
+            r#"CREATE TYPE "user_token_purpose" AS ENUM ('EMAIL_VERIFICATION', 'PASSWORD_RESET')"#.to_owned(),
+        );
+        manager.get_connection().execute(create_enum).await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserToken::Table)
+                    .col(
+                        ColumnDef::new(UserToken::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(UserToken::UserId).integer().not_null())
+                    .col(
+                        ColumnDef::new(UserToken::Purpose)
+                            .custom(UserTokenPurpose::Type)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UserToken::TokenHash)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(UserToken::ExpiresAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(UserToken::UsedAt).timestamp_with_time_zone())
+                    .col(
+                        ColumnDef::new(UserToken::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-user_token-user_id")
+                            .from(UserToken::Table, UserToken::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-user_token-user_id-purpose")
+                    .table(UserToken::Table)
+                    .col(UserToken::UserId)
+                    .col(UserToken::Purpose)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UserToken::Table).to_owned())
+            .await?;
+
+        let drop_enum = Statement::from_string(
+            manager.get_database_backend(),
+            r#"DROP TYPE IF EXISTS "user_token_purpose""#.to_owned(),
+        );
+        manager.get_connection().execute(drop_enum).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(User::VerifiedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum UserTokenPurpose {
+    #[iden = "user_token_purpose"]
+    Type,
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Id,
+    VerifiedAt,
+}
+
+#[derive(Iden)]
+enum UserToken {
+    Table,
+    Id,
+    UserId,
+    Purpose,
+    TokenHash,
+    ExpiresAt,
+    UsedAt,
+    CreatedAt,
+}
@@ -0,0 +1,99 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(ColumnDef::new(User::TotpSecret).string())
+                    .add_column(ColumnDef::new(User::TotpEnabledAt).timestamp_with_time_zone())
+                    .add_column(ColumnDef::new(User::TotpLastUsedStep).big_integer())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(RecoveryCode::Table)
+                    .col(
+                        ColumnDef::new(RecoveryCode::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(RecoveryCode::UserId).integer().not_null())
+                    .col(ColumnDef::new(RecoveryCode::CodeHash).string().not_null())
+                    .col(ColumnDef::new(RecoveryCode::UsedAt).timestamp_with_time_zone())
+                    .col(
+                        ColumnDef::new(RecoveryCode::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-recovery_code-user_id")
+                            .from(RecoveryCode::Table, RecoveryCode::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-recovery_code-user_id-code_hash")
+                    .table(RecoveryCode::Table)
+                    .col(RecoveryCode::UserId)
+                    .col(RecoveryCode::CodeHash)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RecoveryCode::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(User::TotpSecret)
+                    .drop_column(User::TotpEnabledAt)
+                    .drop_column(User::TotpLastUsedStep)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Id,
+    TotpSecret,
+    TotpEnabledAt,
+    TotpLastUsedStep,
+}
+
+#[derive(Iden)]
+enum RecoveryCode {
+    Table,
+    Id,
+    UserId,
+    CodeHash,
+    UsedAt,
+    CreatedAt,
+}
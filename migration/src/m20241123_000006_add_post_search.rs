@@ -0,0 +1,53 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// The text search configuration is part of the generated expression, so the
+// queries in `Repository::search_posts` must use the same one.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let add_column = Statement::from_string(
+            manager.get_database_backend(),
+            r#"ALTER TABLE "post" ADD COLUMN "search_vector" tsvector GENERATED ALWAYS AS (
+                setweight(to_tsvector('english', coalesce("title", '')), 'A') ||
+                setweight(to_tsvector('english', coalesce("content", '')), 'B')
+            ) STORED"#
+                .to_owned(),
+        );
+        manager.get_connection().execute(add_column).await?;
+
+        let create_index = Statement::from_string(
+            manager.get_database_backend(),
+            r#"CREATE INDEX "idx_post_search_vector" ON "post" USING GIN ("search_vector")"#.to_owned(),
+        );
+        manager.get_connection().execute(create_index).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let drop_index = Statement::from_string(
+            manager.get_database_backend(),
+            r#"DROP INDEX IF EXISTS "idx_post_search_vector""#.to_owned(),
+        );
+        manager.get_connection().execute(drop_index).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Post::Table)
+                    .drop_column(Post::SearchVector)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Post {
+    Table,
+    SearchVector,
+}
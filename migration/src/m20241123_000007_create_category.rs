@@ -0,0 +1,120 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Category::Table)
+                    .col(
+                        ColumnDef::new(Category::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Category::Name)
+                            .string_len(100)
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(Category::Description).text())
+                    .col(
+                        ColumnDef::new(Category::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        ColumnDef::new(Category::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(PostCategory::Table)
+                    .col(ColumnDef::new(PostCategory::PostId).integer().not_null())
+                    .col(ColumnDef::new(PostCategory::CategoryId).integer().not_null())
+                    .primary_key(
+                        Index::create()
+                            .col(PostCategory::PostId)
+                            .col(PostCategory::CategoryId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-post_category-post_id")
+                            .from(PostCategory::Table, PostCategory::PostId)
+                            .to(Post::Table, Post::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-post_category-category_id")
+                            .from(PostCategory::Table, PostCategory::CategoryId)
+                            .to(Category::Table, Category::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // The primary key covers lookups by post; listing a category's posts
+        // goes the other way.
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-post_category-category_id")
+                    .table(PostCategory::Table)
+                    .col(PostCategory::CategoryId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PostCategory::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Category::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Post {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Category {
+    Table,
+    Id,
+    #[iden = "category_name"]
+    Name,
+    Description,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Iden)]
+enum PostCategory {
+    Table,
+    PostId,
+    CategoryId,
+}
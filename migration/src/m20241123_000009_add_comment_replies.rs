@@ -0,0 +1,141 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Replies must outlive the comments they answer: a comment with replies can
+// only be removed by turning it into a placeholder, so the parent key
+// restricts deletes. For the same reason comments no longer go away with their
+// author; `Repository::delete_user` blanks them and the key only clears the id.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Comment::Table)
+                    .add_column(ColumnDef::new(Comment::ParentId).integer())
+                    .add_column(
+                        ColumnDef::new(Comment::Depth)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .add_column(ColumnDef::new(Comment::DeletedAt).timestamp_with_time_zone())
+                    .modify_column(ColumnDef::new(Comment::AuthorId).integer().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_foreign_key(
+                ForeignKey::drop()
+                    .name("fk-comment-author_id")
+                    .table(Comment::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk-comment-author_id")
+                    .from(Comment::Table, Comment::AuthorId)
+                    .to(User::Table, User::Id)
+                    .on_delete(ForeignKeyAction::SetNull)
+                    .on_update(ForeignKeyAction::Cascade)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk-comment-parent_id")
+                    .from(Comment::Table, Comment::ParentId)
+                    .to(Comment::Table, Comment::Id)
+                    .on_delete(ForeignKeyAction::Restrict)
+                    .on_update(ForeignKeyAction::Cascade)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-comment-parent_id")
+                    .table(Comment::Table)
+                    .col(Comment::ParentId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_foreign_key(
+                ForeignKey::drop()
+                    .name("fk-comment-parent_id")
+                    .table(Comment::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Comments without an author cannot be kept under the old schema.
+        manager
+            .exec_stmt(
+                Query::delete()
+                    .from_table(Comment::Table)
+                    .and_where(Expr::col(Comment::AuthorId).is_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_foreign_key(
+                ForeignKey::drop()
+                    .name("fk-comment-author_id")
+                    .table(Comment::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk-comment-author_id")
+                    .from(Comment::Table, Comment::AuthorId)
+                    .to(User::Table, User::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .on_update(ForeignKeyAction::Cascade)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Comment::Table)
+                    .drop_column(Comment::ParentId)
+                    .drop_column(Comment::Depth)
+                    .drop_column(Comment::DeletedAt)
+                    .modify_column(ColumnDef::new(Comment::AuthorId).integer().not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Comment {
+    Table,
+    Id,
+    AuthorId,
+    ParentId,
+    Depth,
+    DeletedAt,
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,113 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Replaces `post.published` with a status. Published posts keep their last
+// update time as `published_at`, the closest thing to it on record.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let create_enum = Statement::from_string(
+            manager.get_database_backend(),
+            r#"CREATE TYPE "post_status" AS ENUM ('DRAFT', 'PUBLISHED', 'ARCHIVED')"#.to_owned(),
+        );
+        manager.get_connection().execute(create_enum).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Post::Table)
+                    .add_column(
+                        ColumnDef::new(Post::Status)
+                            .custom(PostStatus::Type)
+                            .not_null()
+                            .default(Expr::cust(r#"'DRAFT'::"post_status""#)),
+                    )
+                    .add_column(ColumnDef::new(Post::PublishedAt).timestamp_with_time_zone())
+                    .to_owned(),
+            )
+            .await?;
+
+        let backfill = Statement::from_string(
+            manager.get_database_backend(),
+            r#"UPDATE "post" SET "post_status" = 'PUBLISHED', "published_at" = "updated_at" WHERE "published""#.to_owned(),
+        );
+        manager.get_connection().execute(backfill).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Post::Table)
+                    .drop_column(Post::Published)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-post-post_status")
+                    .table(Post::Table)
+                    .col(Post::Status)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Post::Table)
+                    .add_column(
+                        ColumnDef::new(Post::Published)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        let backfill = Statement::from_string(
+            manager.get_database_backend(),
+            r#"UPDATE "post" SET "published" = TRUE WHERE "post_status" = 'PUBLISHED'"#.to_owned(),
+        );
+        manager.get_connection().execute(backfill).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Post::Table)
+                    .drop_column(Post::Status)
+                    .drop_column(Post::PublishedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        let drop_enum = Statement::from_string(
+            manager.get_database_backend(),
+            r#"DROP TYPE IF EXISTS "post_status""#.to_owned(),
+        );
+        manager.get_connection().execute(drop_enum).await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum PostStatus {
+    #[iden = "post_status"]
+    Type,
+}
+
+#[derive(Iden)]
+enum Post {
+    Table,
+    Published,
+    #[iden = "post_status"]
+    Status,
+    PublishedAt,
+}
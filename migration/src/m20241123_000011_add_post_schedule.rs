@@ -0,0 +1,46 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Post::Table)
+                    .add_column(ColumnDef::new(Post::ScheduledAt).timestamp_with_time_zone())
+                    .to_owned(),
+            )
+            .await?;
+
+        // The publisher looks for due posts on every tick.
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-post-scheduled_at")
+                    .table(Post::Table)
+                    .col(Post::ScheduledAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Post::Table)
+                    .drop_column(Post::ScheduledAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Post {
+    Table,
+    ScheduledAt,
+}
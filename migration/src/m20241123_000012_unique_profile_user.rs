@@ -0,0 +1,47 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// A user has at most one profile. Extra rows left by earlier versions are
+// dropped, keeping the oldest one, which is the one `GET /users/{id}/profile`
+// returned so far.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let dedupe = Statement::from_string(
+            manager.get_database_backend(),
+            r#"DELETE FROM "profile" p USING "profile" older WHERE p."user_id" = older."user_id" AND p."id" > older."id""#.to_owned(),
+        );
+        manager.get_connection().execute(dedupe).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-profile-user_id")
+                    .table(Profile::Table)
+                    .col(Profile::UserId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-profile-user_id")
+                    .table(Profile::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Profile {
+    Table,
+    UserId,
+}
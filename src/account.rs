@@ -0,0 +1,196 @@
+use actix_web::web;
+use chrono::{Duration, Utc};
+use sea_orm::DbErr;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{generate_opaque_token, hash_opaque_token, AuthSettings};
+use crate::dto::ApiResponse;
+use crate::entities::sea_orm_active_enums::UserTokenPurpose;
+use crate::entities::{user, user_token};
+use crate::error::AppError;
+use crate::mailer::{EmailMessage, Mailer};
+use crate::password::{check_password_strength, hash_password};
+use crate::repository::Repository;
+
+const EMAIL_VERIFICATION_EXPIRATION_HOURS: i64 = 24;
+const PASSWORD_RESET_EXPIRATION_MINUTES: i64 = 60;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerificationEmailRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasswordResetConfirmation {
+    pub token: String,
+    pub new_password: String,
+}
+
+// Creates a fresh single-use token and returns the plaintext; only the hash is stored.
+async fn create_token(
+    repo: &Repository,
+    user_id: i32,
+    purpose: UserTokenPurpose,
+    lifetime: Duration,
+) -> Result<String, DbErr> {
+    // Only the newest link of each kind should work.
+    repo.expire_user_tokens(user_id, purpose.clone()).await?;
+
+    let token = generate_opaque_token();
+    repo.create_user_token(user_id, purpose, hash_opaque_token(&token), Utc::now() + lifetime).await?;
+
+    Ok(token)
+}
+
+pub async fn send_verification_email(
+    repo: &Repository,
+    mailer: &dyn Mailer,
+    settings: &AuthSettings,
+    user: &user::Model,
+) -> Result<(), String> {
+    let token = create_token(
+        repo,
+        user.id,
+        UserTokenPurpose::EmailVerification,
+        Duration::hours(EMAIL_VERIFICATION_EXPIRATION_HOURS),
+    )
+    .await
+    .map_err(|err| err.to_string())?;
+
+    mailer
+        .send(EmailMessage {
+            to: user.email.clone(),
+            subject: "Verify your email address".to_string(),
+            body: format!(
+                "Hi {},\n\nConfirm your email address by opening the link below:\n\n{}/verify-email?token={}\n\nThe link expires in {} hours.",
+                user.first_name, settings.app_base_url, token, EMAIL_VERIFICATION_EXPIRATION_HOURS
+            ),
+        })
+        .await
+}
+
+/// Needs no token, since an unverified account may not be able to log in.
+/// Like the password reset request it always answers the same way.
+pub async fn request_email_verification(
+    body: web::Json<VerificationEmailRequest>,
+    repo: web::Data<Repository>,
+    mailer: web::Data<dyn Mailer>,
+    settings: web::Data<AuthSettings>,
+) -> Result<web::Json<ApiResponse<()>>, AppError> {
+    let user = repo.find_user_by_email(body.email.trim()).await?;
+
+    if let Some(user) = user.filter(|user| user.verified_at.is_none()) {
+        if let Err(err) = send_verification_email(&repo, mailer.get_ref(), &settings, &user).await {
+            log::warn!("Failed to send verification email to user {}: {}", user.id, err);
+        }
+    }
+
+    Ok(web::Json(ApiResponse::success(
+        (),
+        "If the email belongs to an unverified account, a verification link has been sent",
+    )))
+}
+
+// Looks up a live token and burns it.
+async fn consume_token(repo: &Repository, token: &str, purpose: UserTokenPurpose) -> Result<user_token::Model, AppError> {
+    let token = repo
+        .find_live_user_token(&hash_opaque_token(token), purpose)
+        .await?
+        .ok_or_else(|| AppError::validation("Invalid or expired token"))?;
+
+    let used = repo.use_user_token(token.id).await?;
+    if !used {
+        return Err(AppError::validation("Invalid or expired token"));
+    }
+
+    Ok(token)
+}
+
+pub async fn confirm_email_verification(
+    body: web::Json<TokenRequest>,
+    repo: web::Data<Repository>,
+) -> Result<web::Json<ApiResponse<()>>, AppError> {
+    let token = consume_token(&repo, &body.token, UserTokenPurpose::EmailVerification).await?;
+
+    repo.mark_user_verified(token.user_id).await?;
+
+    Ok(web::Json(ApiResponse::success((), "Email address verified")))
+}
+
+/// Always answers the same way so the endpoint cannot be used to probe which
+/// emails have an account.
+pub async fn request_password_reset(
+    body: web::Json<PasswordResetRequest>,
+    repo: web::Data<Repository>,
+    mailer: web::Data<dyn Mailer>,
+    settings: web::Data<AuthSettings>,
+) -> Result<web::Json<ApiResponse<()>>, AppError> {
+    let user = repo.find_user_by_email(body.email.trim()).await?;
+
+    if let Some(user) = user {
+        let token = create_token(
+            &repo,
+            user.id,
+            UserTokenPurpose::PasswordReset,
+            Duration::minutes(PASSWORD_RESET_EXPIRATION_MINUTES),
+        )
+        .await?;
+
+        let sent = mailer
+            .send(EmailMessage {
+                to: user.email.clone(),
+                subject: "Reset your password".to_string(),
+                body: format!(
+                    "Hi {},\n\nSomeone asked to reset the password of your account. If it was you, open the link below:\n\n{}/reset-password?token={}\n\nThe link expires in {} minutes. If you did not ask for this, ignore this email.",
+                    user.first_name, settings.app_base_url, token, PASSWORD_RESET_EXPIRATION_MINUTES
+                ),
+            })
+            .await;
+        if let Err(err) = sent {
+            log::warn!("Failed to send password reset email to user {}: {}", user.id, err);
+        }
+    }
+
+    Ok(web::Json(ApiResponse::success(
+        (),
+        "If the email belongs to an account, a reset link has been sent",
+    )))
+}
+
+pub async fn confirm_password_reset(
+    body: web::Json<PasswordResetConfirmation>,
+    repo: web::Data<Repository>,
+) -> Result<web::Json<ApiResponse<()>>, AppError> {
+    let token = repo
+        .find_live_user_token(&hash_opaque_token(&body.token), UserTokenPurpose::PasswordReset)
+        .await?
+        .ok_or_else(|| AppError::validation("Invalid or expired token"))?;
+    let user = repo
+        .find_user_by_id(token.user_id)
+        .await?
+        .ok_or_else(|| AppError::validation("Invalid or expired token"))?;
+
+    // Check the new password before burning the token so a rejected password
+    // does not force the user to request another link.
+    check_password_strength(&body.new_password, &user.email).map_err(AppError::Validation)?;
+    consume_token(&repo, &body.token, UserTokenPurpose::PasswordReset).await?;
+
+    let password_hash = hash_password(&body.new_password)
+        .map_err(|err| AppError::internal(format!("Failed to hash password: {}", err)))?;
+    repo.set_user_password(user.id, password_hash).await?;
+
+    // Whoever knew the old password may hold live sessions; end them all.
+    repo.revoke_user_refresh_tokens(user.id).await?;
+
+    Ok(web::Json(ApiResponse::success((), "Password has been reset")))
+}
@@ -1,182 +1,894 @@
-use actix_web::{web, HttpResponse, Responder};
-use sea_orm::DatabaseConnection;
-use crate::entities::{user, profile, post};
-use crate::repository::Repository;
-use crate::dto::{UserCreateDto, ApiResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
+use sea_orm::{ActiveEnum, DatabaseConnection, SqlErr};
+use crate::entities::{user, profile, post, category, comment};
+use crate::error::AppError;
+use crate::repository::{PostScope, Repository, MAX_COMMENT_DEPTH};
+use crate::dto::{
+    ApiResponse, CategoryCreateDto, CategoryPatchDto, CategoryResponseDto, CategoryUpdateDto, CommentCreateDto,
+    CommentNodeDto, CommentResponseDto, CommentUpdateDto, PostCreateDto, PostPatchDto, PostResponseDto, PostScheduleDto, PostSearchResultDto, PostUpdateDto, ProfileCreateDto, ProfilePatchDto,
+    ProfileResponseDto, ProfileUpdateDto, UserCreateDto, UserPatchDto, UserResponseDto, UserUpdateDto,
+};
+use crate::account;
+use crate::auth::{AuthMiddleware, AuthSettings, ReadAccess};
+use crate::mailer::Mailer;
+use crate::password::check_password_strength;
+use crate::entities::sea_orm_active_enums::PostStatus;
+use crate::permissions::{
+    AdminOrModerator, RequireRole, CHANGE_USER_ROLE, MANAGE_CATEGORIES, MANAGE_OTHERS, MODERATE_COMMENTS, MODERATE_POSTS,
+};
+use crate::filters::{
+    parse_include, CommentListQuery, CommentThreadQuery, IncludeQuery, PostInclude, PostListQuery, PostSearchQuery, PostVisibility, Projection, UserInclude,
+    UserListQuery,
+    POST_INCLUDES, USER_INCLUDES,
+};
+use crate::pagination::{PageMeta, PageQuery, PageRequest};
+use crate::validation::ValidatedJson;
 
 // User handlers
 
-pub async fn get_users(db: web::Data<DatabaseConnection>) -> impl Responder {
+pub async fn get_users(
+    _access: ReadAccess,
+    auth: Result<AuthMiddleware, AppError>,
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    query: web::Query<UserListQuery>,
+) -> Result<HttpResponse, AppError> {
+    let (filter, request, projection) = query.into_inner().into_parts()?;
     let repo = Repository::new(db.get_ref().clone());
-    match repo.find_all_users().await {
-        Ok(users) => HttpResponse::Ok().json(ApiResponse::success(users, "Users retrieved successfully")),
-        Err(err) => HttpResponse::InternalServerError().json(
-            ApiResponse::<Vec<user::Model>>::error(500, &format!("Error retrieving users: {}", err))
-        ),
+    match projection {
+        Projection::Full(include) => {
+            let page = repo.find_users_page(&filter, request).await?;
+            let meta = PageMeta::new(&req, &request, &page);
+            let users = user_responses(&repo, page.items, &include, post_visibility(auth.as_ref().ok())).await?;
+            Ok(HttpResponse::Ok().json(ApiResponse::success(users, "Users retrieved successfully").with_meta(meta)))
+        }
+        Projection::Fields(fields) => {
+            let page = repo.find_user_fields_page(&filter, request, &fields).await?;
+            let meta = PageMeta::new(&req, &request, &page);
+            Ok(HttpResponse::Ok().json(ApiResponse::success(page.items, "Users retrieved successfully").with_meta(meta)))
+        }
     }
 }
 
-pub async fn create_user(db: web::Data<DatabaseConnection>, user_data: web::Json<UserCreateDto>) -> impl Responder {
+/// Admin-only; everyone else signs up through `/auth/register`.
+pub async fn create_user(auth: AuthMiddleware, db: web::Data<DatabaseConnection>, user_data: ValidatedJson<UserCreateDto>) -> Result<HttpResponse, AppError> {
+    auth.require_any_role(CHANGE_USER_ROLE)?;
+    // Same rules as self-registration, so both ways of creating an account agree.
+    check_password_strength(&user_data.password, &user_data.email).map_err(AppError::Validation)?;
+
     let repo = Repository::new(db.get_ref().clone());
-    match repo.create_user(user_data.0).await {
-        Ok(user) => HttpResponse::Created().json(ApiResponse::success(user, "User created successfully")),
-        Err(err) => HttpResponse::InternalServerError().json(
-            ApiResponse::<user::Model>::error(500, &format!("Error creating user: {}", err))
-        ),
-    }
+    let user = repo.create_user(user_data.into_inner()).await?;
+    Ok(HttpResponse::Created().json(ApiResponse::success(UserResponseDto::from(user), "User created successfully")))
 }
 
-pub async fn get_user(db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> impl Responder {
+pub async fn get_user(
+    _access: ReadAccess,
+    auth: Result<AuthMiddleware, AppError>,
+    db: web::Data<DatabaseConnection>,
+    id: web::Path<i32>,
+    query: web::Query<IncludeQuery>,
+) -> Result<HttpResponse, AppError> {
+    let include = parse_include(query.include.as_deref(), USER_INCLUDES)?;
     let repo = Repository::new(db.get_ref().clone());
-    match repo.find_user_by_id(id.into_inner()).await {
-        Ok(Some(user)) => HttpResponse::Ok().json(ApiResponse::success(user, "User found")),
-        Ok(None) => HttpResponse::NotFound().json(
-            ApiResponse::<user::Model>::error(404, "User not found")
-        ),
-        Err(err) => HttpResponse::InternalServerError().json(
-            ApiResponse::<user::Model>::error(500, &format!("Error retrieving user: {}", err))
-        ),
-    }
+    let user = find_user(&repo, id.into_inner()).await?;
+    let user = user_responses(&repo, vec![user], &include, post_visibility(auth.as_ref().ok())).await?.remove(0);
+    Ok(HttpResponse::Ok().json(ApiResponse::success(user, "User found")))
 }
 
 pub async fn update_user(
+    auth: AuthMiddleware,
     db: web::Data<DatabaseConnection>,
+    mailer: web::Data<dyn Mailer>,
+    settings: web::Data<AuthSettings>,
     id: web::Path<i32>,
-    user: web::Json<user::Model>,
-) -> impl Responder {
+    user: ValidatedJson<UserUpdateDto>,
+) -> Result<HttpResponse, AppError> {
+    let id = id.into_inner();
+    auth.require_role_or_owner(MANAGE_OTHERS, id)?;
+
     let repo = Repository::new(db.get_ref().clone());
-    match repo.update_user(id.into_inner(), user.0).await {
-        Ok(user) => HttpResponse::Ok().json(ApiResponse::success(user, "User updated successfully")),
-        Err(err) => HttpResponse::InternalServerError().json(
-            ApiResponse::<user::Model>::error(500, &format!("Error updating user: {}", err))
-        ),
+    let existing = find_user(&repo, id).await?;
+    if user.user_role != existing.user_role {
+        auth.require_any_role(CHANGE_USER_ROLE)?;
     }
+
+    let user = repo.update_user(&existing, user.into_inner()).await?;
+    verify_new_email(&repo, mailer.get_ref(), &settings, &existing, &user).await;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(UserResponseDto::from(user), "User updated successfully")))
 }
 
-pub async fn delete_user(db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> impl Responder {
+pub async fn patch_user(
+    auth: AuthMiddleware,
+    db: web::Data<DatabaseConnection>,
+    mailer: web::Data<dyn Mailer>,
+    settings: web::Data<AuthSettings>,
+    id: web::Path<i32>,
+    user: ValidatedJson<UserPatchDto>,
+) -> Result<HttpResponse, AppError> {
+    let id = id.into_inner();
+    auth.require_role_or_owner(MANAGE_OTHERS, id)?;
+
     let repo = Repository::new(db.get_ref().clone());
-    match repo.delete_user(id.into_inner()).await {
-        Ok(_) => HttpResponse::Ok().json(ApiResponse::<()>::success((), "User deleted successfully")),
-        Err(err) => HttpResponse::InternalServerError().json(
-            ApiResponse::<()>::error(500, &format!("Error deleting user: {}", err))
-        ),
+    let existing = find_user(&repo, id).await?;
+    if user.user_role.as_ref().is_some_and(|role| *role != existing.user_role) {
+        auth.require_any_role(CHANGE_USER_ROLE)?;
     }
+
+    let user = repo.patch_user(&existing, user.into_inner()).await?;
+    verify_new_email(&repo, mailer.get_ref(), &settings, &existing, &user).await;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(UserResponseDto::from(user), "User updated successfully")))
+}
+
+/// Sends a verification link when the email address changed. Like on sign-up,
+/// a mail failure does not fail the update; a new link can be requested.
+async fn verify_new_email(repo: &Repository, mailer: &dyn Mailer, settings: &AuthSettings, before: &user::Model, after: &user::Model) {
+    if before.email == after.email {
+        return;
+    }
+    if let Err(err) = account::send_verification_email(repo, mailer, settings, after).await {
+        log::warn!("Failed to send verification email to user {}: {}", after.id, err);
+    }
+}
+
+pub async fn delete_user(auth: AuthMiddleware, db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> Result<HttpResponse, AppError> {
+    let id = id.into_inner();
+    auth.require_role_or_owner(MANAGE_OTHERS, id)?;
+
+    let repo = Repository::new(db.get_ref().clone());
+    let result = repo.delete_user(id).await?;
+    if result.rows_affected == 0 {
+        return Err(AppError::not_found("User not found"));
+    }
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::success((), "User deleted successfully")))
+}
+
+// Mounted under the admin scope, which already checks the caller's role.
+pub async fn unlock_user(db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let user = repo.reset_failed_logins(id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(UserResponseDto::from(user), "User unlocked successfully")))
+}
+
+// Nested under /users/{id}; a missing user is a 404 rather than an empty list.
+
+pub async fn get_user_posts(
+    _access: ReadAccess,
+    auth: Result<AuthMiddleware, AppError>,
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    id: web::Path<i32>,
+    query: web::Query<PostListQuery>,
+) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let user = find_user(&repo, id.into_inner()).await?;
+    list_posts(&req, &repo, PostScope::ByAuthor(&user), query.into_inner(), post_visibility(auth.as_ref().ok())).await
+}
+
+pub async fn create_user_post(
+    auth: AuthMiddleware,
+    db: web::Data<DatabaseConnection>,
+    id: web::Path<i32>,
+    post: ValidatedJson<PostCreateDto>,
+) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let user = find_user(&repo, id.into_inner()).await?;
+    auth.require_role_or_owner(MANAGE_OTHERS, user.id)?;
+
+    let post = repo.create_post(user.id, post.into_inner()).await?;
+    Ok(HttpResponse::Created().json(ApiResponse::success(PostResponseDto::from(post), "Post created successfully")))
+}
+
+pub async fn get_user_profile(_access: ReadAccess, db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let user = find_user(&repo, id.into_inner()).await?;
+    let profile = repo
+        .find_profile_of_user(&user)
+        .await?
+        .ok_or_else(|| AppError::not_found("Profile not found"))?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(ProfileResponseDto::from(profile), "Profile found")))
+}
+
+/// Converts users to responses, embedding the requested relations.
+async fn user_responses(
+    repo: &Repository,
+    users: Vec<user::Model>,
+    include: &[UserInclude],
+    visibility: PostVisibility,
+) -> Result<Vec<UserResponseDto>, AppError> {
+    let mut profiles = if include.contains(&UserInclude::Profile) {
+        Some(repo.load_user_profiles(&users).await?.into_iter())
+    } else {
+        None
+    };
+    let mut posts = if include.contains(&UserInclude::Posts) {
+        // Converted in one batch, then split back per user.
+        let posts = repo.load_user_posts(&users, visibility).await?;
+        let lengths: Vec<usize> = posts.iter().map(Vec::len).collect();
+        let mut responses = post_responses(repo, posts.into_iter().flatten().collect(), &[]).await?.into_iter();
+        let posts: Vec<Vec<PostResponseDto>> = lengths.into_iter().map(|len| responses.by_ref().take(len).collect()).collect();
+        Some(posts.into_iter())
+    } else {
+        None
+    };
+
+    Ok(users
+        .into_iter()
+        .map(|user| {
+            let mut response = UserResponseDto::from(user);
+            response.profile = profiles.as_mut().and_then(Iterator::next).map(|profile| profile.map(ProfileResponseDto::from));
+            response.posts = posts.as_mut().and_then(Iterator::next);
+            response
+        })
+        .collect())
+}
+
+async fn find_user(repo: &Repository, id: i32) -> Result<user::Model, AppError> {
+    repo.find_user_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::not_found("User not found"))
 }
 
 // Profile handlers
 
-pub async fn get_profiles(db: web::Data<DatabaseConnection>) -> impl Responder {
+pub async fn get_profiles(
+    _access: ReadAccess,
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    query: web::Query<PageQuery>,
+) -> Result<HttpResponse, AppError> {
+    let request = PageRequest::try_from(&query.into_inner())?;
+    let repo = Repository::new(db.get_ref().clone());
+    let page = repo.find_profiles_page(request).await?.map(ProfileResponseDto::from);
+    let meta = PageMeta::new(&req, &request, &page);
+    Ok(HttpResponse::Ok().json(ApiResponse::success(page.items, "Profiles retrieved successfully").with_meta(meta)))
+}
+
+pub async fn create_profile(auth: AuthMiddleware, db: web::Data<DatabaseConnection>, profile: ValidatedJson<ProfileCreateDto>) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let profile = repo
+        .create_profile(auth.user_id, profile.into_inner())
+        .await
+        .map_err(|err| match err.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => AppError::conflict("User already has a profile"),
+            _ => AppError::from(err),
+        })?;
+    Ok(HttpResponse::Created().json(ApiResponse::success(ProfileResponseDto::from(profile), "Profile created successfully")))
+}
+
+pub async fn get_profile(_access: ReadAccess, db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let profile = find_profile(&repo, id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(ProfileResponseDto::from(profile), "Profile found")))
+}
+
+pub async fn update_profile(
+    auth: AuthMiddleware,
+    db: web::Data<DatabaseConnection>,
+    id: web::Path<i32>,
+    profile: ValidatedJson<ProfileUpdateDto>,
+) -> Result<HttpResponse, AppError> {
     let repo = Repository::new(db.get_ref().clone());
-    match repo.find_all_profiles().await {
-        Ok(profiles) => HttpResponse::Ok().json(ApiResponse::success(profiles, "Profiles retrieved successfully")),
-        Err(err) => HttpResponse::InternalServerError().json(
-            ApiResponse::<Vec<profile::Model>>::error(500, &format!("Error retrieving profiles: {}", err))
-        ),
+    let existing = find_profile(&repo, id.into_inner()).await?;
+    auth.require_role_or_owner(MANAGE_OTHERS, existing.user_id)?;
+
+    let profile = repo.update_profile(existing.id, profile.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(ProfileResponseDto::from(profile), "Profile updated successfully")))
+}
+
+pub async fn patch_profile(
+    auth: AuthMiddleware,
+    db: web::Data<DatabaseConnection>,
+    id: web::Path<i32>,
+    profile: ValidatedJson<ProfilePatchDto>,
+) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let existing = find_profile(&repo, id.into_inner()).await?;
+    auth.require_role_or_owner(MANAGE_OTHERS, existing.user_id)?;
+
+    let profile = repo.patch_profile(existing.id, profile.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(ProfileResponseDto::from(profile), "Profile updated successfully")))
+}
+
+pub async fn delete_profile(auth: AuthMiddleware, db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let existing = find_profile(&repo, id.into_inner()).await?;
+    auth.require_role_or_owner(MANAGE_OTHERS, existing.user_id)?;
+
+    repo.delete_profile(existing.id).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::success((), "Profile deleted successfully")))
+}
+
+async fn find_profile(repo: &Repository, id: i32) -> Result<profile::Model, AppError> {
+    repo.find_profile_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Profile not found"))
+}
+
+// Post handlers
+
+pub async fn get_posts(
+    _access: ReadAccess,
+    auth: Result<AuthMiddleware, AppError>,
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    query: web::Query<PostListQuery>,
+) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    list_posts(&req, &repo, PostScope::All, query.into_inner(), post_visibility(auth.as_ref().ok())).await
+}
+
+/// Shared by every endpoint that lists posts: `/posts`, `/users/{id}/posts`
+/// and `/categories/{id}/posts`.
+async fn list_posts(
+    req: &HttpRequest,
+    repo: &Repository,
+    scope: PostScope<'_>,
+    query: PostListQuery,
+    visibility: PostVisibility,
+) -> Result<HttpResponse, AppError> {
+    let (filter, request, projection) = query.into_parts(visibility)?;
+    match projection {
+        Projection::Full(include) => {
+            let page = repo.find_posts_page(scope, &filter, request).await?;
+            let meta = PageMeta::new(req, &request, &page);
+            let posts = post_responses(repo, page.items, &include).await?;
+            Ok(HttpResponse::Ok().json(ApiResponse::success(posts, "Posts retrieved successfully").with_meta(meta)))
+        }
+        Projection::Fields(fields) => {
+            let page = repo.find_post_fields_page(scope, &filter, request, &fields).await?;
+            let meta = PageMeta::new(req, &request, &page);
+            Ok(HttpResponse::Ok().json(ApiResponse::success(page.items, "Posts retrieved successfully").with_meta(meta)))
+        }
     }
 }
 
-pub async fn create_profile(db: web::Data<DatabaseConnection>, profile: web::Json<profile::Model>) -> impl Responder {
+pub async fn search_posts(
+    _access: ReadAccess,
+    auth: Result<AuthMiddleware, AppError>,
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    query: web::Query<PostSearchQuery>,
+) -> Result<HttpResponse, AppError> {
+    let (q, filter, request) = query.into_inner().into_parts(post_visibility(auth.as_ref().ok()))?;
     let repo = Repository::new(db.get_ref().clone());
-    match repo.create_profile(profile.0).await {
-        Ok(profile) => HttpResponse::Created().json(ApiResponse::success(profile, "Profile created successfully")),
-        Err(err) => HttpResponse::InternalServerError().json(
-            ApiResponse::<profile::Model>::error(500, &format!("Error creating profile: {}", err))
-        ),
+    let mut page = repo.search_posts(&q, &filter, request).await?.map(PostSearchResultDto::from);
+    let ids: Vec<i32> = page.items.iter().map(|result| result.post.id).collect();
+    let comment_counts = repo.count_visible_comments(&ids).await?;
+    for result in &mut page.items {
+        result.post.comment_count = comment_counts.get(&result.post.id).copied().unwrap_or(0);
     }
+    let meta = PageMeta::new(&req, &request, &page);
+    Ok(HttpResponse::Ok().json(ApiResponse::success(page.items, "Posts retrieved successfully").with_meta(meta)))
+}
+
+pub async fn create_post(auth: AuthMiddleware, db: web::Data<DatabaseConnection>, post: ValidatedJson<PostCreateDto>) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let post = repo.create_post(auth.user_id, post.into_inner()).await?;
+    Ok(HttpResponse::Created().json(ApiResponse::success(PostResponseDto::from(post), "Post created successfully")))
+}
+
+pub async fn get_post(
+    _access: ReadAccess,
+    auth: Result<AuthMiddleware, AppError>,
+    db: web::Data<DatabaseConnection>,
+    id: web::Path<i32>,
+    query: web::Query<IncludeQuery>,
+) -> Result<HttpResponse, AppError> {
+    let include = parse_include(query.include.as_deref(), POST_INCLUDES)?;
+    let repo = Repository::new(db.get_ref().clone());
+    let post = find_visible_post(&repo, id.into_inner(), post_visibility(auth.as_ref().ok())).await?;
+    let post = post_responses(&repo, vec![post], &include).await?.remove(0);
+    Ok(HttpResponse::Ok().json(ApiResponse::success(post, "Post found")))
+}
+
+pub async fn update_post(
+    auth: AuthMiddleware,
+    db: web::Data<DatabaseConnection>,
+    id: web::Path<i32>,
+    post: ValidatedJson<PostUpdateDto>,
+) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let existing = find_post(&repo, id.into_inner()).await?;
+    auth.require_role_or_owner(MANAGE_OTHERS, existing.author_id)?;
+
+    let post = repo.update_post(existing.id, post.into_inner()).await?;
+    let post = post_response(&repo, post).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(post, "Post updated successfully")))
+}
+
+pub async fn patch_post(
+    auth: AuthMiddleware,
+    db: web::Data<DatabaseConnection>,
+    id: web::Path<i32>,
+    post: ValidatedJson<PostPatchDto>,
+) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let existing = find_post(&repo, id.into_inner()).await?;
+    auth.require_role_or_owner(MANAGE_OTHERS, existing.author_id)?;
+
+    let post = repo.patch_post(existing.id, post.into_inner()).await?;
+    let post = post_response(&repo, post).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(post, "Post updated successfully")))
+}
+
+pub async fn delete_post(auth: AuthMiddleware, db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let existing = find_post(&repo, id.into_inner()).await?;
+    auth.require_role_or_owner(MANAGE_OTHERS, existing.author_id)?;
+
+    repo.delete_post(existing.id).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::success((), "Post deleted successfully")))
+}
+
+pub async fn publish_post(auth: AuthMiddleware, db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let post = find_post(&repo, id.into_inner()).await?;
+    auth.require_role_or_owner(MANAGE_OTHERS, post.author_id)?;
+
+    let post = transition_post(&repo, post, &[PostStatus::Draft], PostStatus::Published).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(post, "Post published successfully")))
+}
+
+pub async fn archive_post(auth: AuthMiddleware, db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let post = find_post(&repo, id.into_inner()).await?;
+    auth.require_role_or_owner(MODERATE_POSTS, post.author_id)?;
+
+    let post = transition_post(&repo, post, &[PostStatus::Draft, PostStatus::Published], PostStatus::Archived).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(post, "Post archived successfully")))
+}
+
+/// Takes a published or archived post back to draft.
+pub async fn unpublish_post(auth: AuthMiddleware, db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let post = find_post(&repo, id.into_inner()).await?;
+    auth.require_role_or_owner(MODERATE_POSTS, post.author_id)?;
+
+    let post = transition_post(&repo, post, &[PostStatus::Published, PostStatus::Archived], PostStatus::Draft).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(post, "Post unpublished successfully")))
+}
+
+/// Sets the time a draft is published by the background publisher.
+pub async fn schedule_post(auth: AuthMiddleware, db: web::Data<DatabaseConnection>, id: web::Path<i32>, schedule: ValidatedJson<PostScheduleDto>) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let post = find_post(&repo, id.into_inner()).await?;
+    auth.require_role_or_owner(MANAGE_OTHERS, post.author_id)?;
+
+    let post = repo
+        .schedule_post(post.id, Some(schedule.into_inner().scheduled_at))
+        .await?
+        .ok_or_else(|| AppError::conflict("Only drafts can be scheduled"))?;
+    let post = post_response(&repo, post).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(post, "Post scheduled successfully")))
 }
 
-pub async fn get_profile(db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> impl Responder {
+pub async fn unschedule_post(auth: AuthMiddleware, db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> Result<HttpResponse, AppError> {
     let repo = Repository::new(db.get_ref().clone());
-    match repo.find_profile_by_id(id.into_inner()).await {
-        Ok(Some(profile)) => HttpResponse::Ok().json(ApiResponse::success(profile, "Profile found")),
-        Ok(None) => HttpResponse::NotFound().json(
-            ApiResponse::<profile::Model>::error(404, "Profile not found")
-        ),
-        Err(err) => HttpResponse::InternalServerError().json(
-            ApiResponse::<profile::Model>::error(500, &format!("Error retrieving profile: {}", err))
-        ),
+    let post = find_post(&repo, id.into_inner()).await?;
+    auth.require_role_or_owner(MANAGE_OTHERS, post.author_id)?;
+
+    let post = repo
+        .schedule_post(post.id, None)
+        .await?
+        .ok_or_else(|| AppError::conflict("Only scheduled drafts can be unscheduled"))?;
+    let post = post_response(&repo, post).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(post, "Post schedule cancelled")))
+}
+
+/// Moves the post to `to`, or answers 409 if it is not in one of the `from` states.
+async fn transition_post(repo: &Repository, post: post::Model, from: &[PostStatus], to: PostStatus) -> Result<PostResponseDto, AppError> {
+    match repo.transition_post(post.id, from, to.clone()).await? {
+        Some(post) => post_response(repo, post).await,
+        None => Err(AppError::conflict(format!(
+            "A {} post cannot be moved to {}",
+            post.post_status.to_value(),
+            to.to_value()
+        ))),
     }
 }
 
-pub async fn update_profile(
+/// Converts posts to responses, embedding the requested relations.
+async fn post_responses(repo: &Repository, posts: Vec<post::Model>, include: &[PostInclude]) -> Result<Vec<PostResponseDto>, AppError> {
+    let mut authors = if include.contains(&PostInclude::Author) {
+        Some(repo.load_post_authors(&posts).await?.into_iter())
+    } else {
+        None
+    };
+    let ids: Vec<i32> = posts.iter().map(|post| post.id).collect();
+    let comment_counts = repo.count_visible_comments(&ids).await?;
+
+    Ok(posts
+        .into_iter()
+        .map(|post| {
+            let mut response = PostResponseDto::from(post);
+            response.comment_count = comment_counts.get(&response.id).copied().unwrap_or(0);
+            response.author = authors.as_mut().and_then(Iterator::next).flatten().map(UserResponseDto::from);
+            response
+        })
+        .collect())
+}
+
+async fn post_response(repo: &Repository, post: post::Model) -> Result<PostResponseDto, AppError> {
+    Ok(post_responses(repo, vec![post], &[]).await?.remove(0))
+}
+
+async fn find_post(repo: &Repository, id: i32) -> Result<post::Model, AppError> {
+    repo.find_post_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Post not found"))
+}
+
+/// Like `find_post`, but a post the reader may not see is reported as missing.
+async fn find_visible_post(repo: &Repository, id: i32, visibility: PostVisibility) -> Result<post::Model, AppError> {
+    repo.find_post_by_id(id)
+        .await?
+        .filter(|post| visibility.allows(post))
+        .ok_or_else(|| AppError::not_found("Post not found"))
+}
+
+/// Anonymous readers only see published posts; signed-in users also see
+/// their own, and whoever may moderate posts sees everything.
+fn post_visibility(auth: Option<&AuthMiddleware>) -> PostVisibility {
+    match auth {
+        Some(auth) if auth.has_any_role(MODERATE_POSTS) => PostVisibility::All,
+        Some(auth) => PostVisibility::PublishedOrAuthor(auth.user_id),
+        None => PostVisibility::Published,
+    }
+}
+
+// Category handlers
+
+pub async fn get_categories(
+    _access: ReadAccess,
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    query: web::Query<PageQuery>,
+) -> Result<HttpResponse, AppError> {
+    let request = PageRequest::try_from(&query.into_inner())?;
+    let repo = Repository::new(db.get_ref().clone());
+    let page = repo.find_categories_page(request).await?.map(CategoryResponseDto::from);
+    let meta = PageMeta::new(&req, &request, &page);
+    Ok(HttpResponse::Ok().json(ApiResponse::success(page.items, "Categories retrieved successfully").with_meta(meta)))
+}
+
+pub async fn create_category(
+    auth: AuthMiddleware,
+    db: web::Data<DatabaseConnection>,
+    category: ValidatedJson<CategoryCreateDto>,
+) -> Result<HttpResponse, AppError> {
+    auth.require_any_role(MANAGE_CATEGORIES)?;
+
+    let repo = Repository::new(db.get_ref().clone());
+    let category = repo.create_category(category.into_inner()).await?;
+    Ok(HttpResponse::Created().json(ApiResponse::success(CategoryResponseDto::from(category), "Category created successfully")))
+}
+
+pub async fn get_category(_access: ReadAccess, db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let category = find_category(&repo, id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(CategoryResponseDto::from(category), "Category found")))
+}
+
+pub async fn update_category(
+    auth: AuthMiddleware,
     db: web::Data<DatabaseConnection>,
     id: web::Path<i32>,
-    profile: web::Json<profile::Model>,
-) -> impl Responder {
+    category: ValidatedJson<CategoryUpdateDto>,
+) -> Result<HttpResponse, AppError> {
+    auth.require_any_role(MANAGE_CATEGORIES)?;
+
+    let repo = Repository::new(db.get_ref().clone());
+    let existing = find_category(&repo, id.into_inner()).await?;
+    let category = repo.update_category(existing.id, category.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(CategoryResponseDto::from(category), "Category updated successfully")))
+}
+
+pub async fn patch_category(
+    auth: AuthMiddleware,
+    db: web::Data<DatabaseConnection>,
+    id: web::Path<i32>,
+    category: ValidatedJson<CategoryPatchDto>,
+) -> Result<HttpResponse, AppError> {
+    auth.require_any_role(MANAGE_CATEGORIES)?;
+
     let repo = Repository::new(db.get_ref().clone());
-    match repo.update_profile(id.into_inner(), profile.0).await {
-        Ok(profile) => HttpResponse::Ok().json(ApiResponse::success(profile, "Profile updated successfully")),
-        Err(err) => HttpResponse::InternalServerError().json(
-            ApiResponse::<profile::Model>::error(500, &format!("Error updating profile: {}", err))
-        ),
+    let existing = find_category(&repo, id.into_inner()).await?;
+    let category = repo.patch_category(existing.id, category.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(CategoryResponseDto::from(category), "Category updated successfully")))
+}
+
+pub async fn delete_category(auth: AuthMiddleware, db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> Result<HttpResponse, AppError> {
+    auth.require_any_role(MANAGE_CATEGORIES)?;
+
+    let repo = Repository::new(db.get_ref().clone());
+    let result = repo.delete_category(id.into_inner()).await?;
+    if result.rows_affected == 0 {
+        return Err(AppError::not_found("Category not found"));
     }
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::success((), "Category deleted successfully")))
+}
+
+pub async fn get_category_posts(
+    _access: ReadAccess,
+    auth: Result<AuthMiddleware, AppError>,
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    id: web::Path<i32>,
+    query: web::Query<PostListQuery>,
+) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let category = find_category(&repo, id.into_inner()).await?;
+    list_posts(&req, &repo, PostScope::InCategory(&category), query.into_inner(), post_visibility(auth.as_ref().ok())).await
 }
 
-pub async fn delete_profile(db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> impl Responder {
+pub async fn get_post_categories(
+    _access: ReadAccess,
+    auth: Result<AuthMiddleware, AppError>,
+    db: web::Data<DatabaseConnection>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
     let repo = Repository::new(db.get_ref().clone());
-    match repo.delete_profile(id.into_inner()).await {
-        Ok(_) => HttpResponse::Ok().json(ApiResponse::<()>::success((), "Profile deleted successfully")),
-        Err(err) => HttpResponse::InternalServerError().json(
-            ApiResponse::<()>::error(500, &format!("Error deleting profile: {}", err))
-        ),
+    let post = find_visible_post(&repo, id.into_inner(), post_visibility(auth.as_ref().ok())).await?;
+    let categories: Vec<CategoryResponseDto> = repo
+        .find_categories_of_post(&post)
+        .await?
+        .into_iter()
+        .map(CategoryResponseDto::from)
+        .collect();
+    Ok(HttpResponse::Ok().json(ApiResponse::success(categories, "Categories retrieved successfully")))
+}
+
+pub async fn assign_post_category(
+    auth: AuthMiddleware,
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, AppError> {
+    let (post_id, category_id) = path.into_inner();
+    let repo = Repository::new(db.get_ref().clone());
+    let post = find_post(&repo, post_id).await?;
+    auth.require_role_or_owner(MANAGE_CATEGORIES, post.author_id)?;
+
+    let category = find_category(&repo, category_id).await?;
+    repo.assign_category(post.id, category.id).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(CategoryResponseDto::from(category), "Category assigned successfully")))
+}
+
+pub async fn unassign_post_category(
+    auth: AuthMiddleware,
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, AppError> {
+    let (post_id, category_id) = path.into_inner();
+    let repo = Repository::new(db.get_ref().clone());
+    let post = find_post(&repo, post_id).await?;
+    auth.require_role_or_owner(MANAGE_CATEGORIES, post.author_id)?;
+
+    let result = repo.unassign_category(post.id, category_id).await?;
+    if result.rows_affected == 0 {
+        return Err(AppError::not_found("Category is not assigned to this post"));
     }
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::success((), "Category unassigned successfully")))
 }
 
-// Post handlers
+async fn find_category(repo: &Repository, id: i32) -> Result<category::Model, AppError> {
+    repo.find_category_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Category not found"))
+}
+
+// Comment handlers
+
+pub async fn get_post_comments(
+    _access: ReadAccess,
+    auth: Result<AuthMiddleware, AppError>,
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    id: web::Path<i32>,
+    query: web::Query<CommentListQuery>,
+) -> Result<HttpResponse, AppError> {
+    let (request, include_hidden) = query.into_inner().into_parts()?;
+    let visibility = post_visibility(auth.as_ref().ok());
+    if include_hidden {
+        auth?.require_any_role(MODERATE_COMMENTS)?;
+    }
 
-pub async fn get_posts(db: web::Data<DatabaseConnection>) -> impl Responder {
     let repo = Repository::new(db.get_ref().clone());
-    match repo.find_all_posts().await {
-        Ok(posts) => HttpResponse::Ok().json(ApiResponse::success(posts, "Posts retrieved successfully")),
-        Err(err) => HttpResponse::InternalServerError().json(
-            ApiResponse::<Vec<post::Model>>::error(500, &format!("Error retrieving posts: {}", err))
-        ),
+    let post = find_visible_post(&repo, id.into_inner(), visibility).await?;
+    let page = repo.find_comments_page(&post, include_hidden, request).await?.map(CommentResponseDto::from);
+    let meta = PageMeta::new(&req, &request, &page);
+    Ok(HttpResponse::Ok().json(ApiResponse::success(page.items, "Comments retrieved successfully").with_meta(meta)))
+}
+
+pub async fn create_comment(
+    auth: AuthMiddleware,
+    db: web::Data<DatabaseConnection>,
+    id: web::Path<i32>,
+    comment: ValidatedJson<CommentCreateDto>,
+) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let post = find_visible_post(&repo, id.into_inner(), post_visibility(Some(&auth))).await?;
+    if post.post_status != PostStatus::Published {
+        return Err(AppError::conflict("Only published posts can be commented on"));
+    }
+    let depth = match comment.parent_id {
+        None => 0,
+        Some(parent_id) => {
+            let parent = repo
+                .find_comment_by_id(parent_id)
+                .await?
+                .filter(|parent| parent.post_id == post.id)
+                .ok_or_else(|| AppError::unprocessable("parent_id does not refer to a comment on this post"))?;
+            reply_depth(&parent)?
+        }
+    };
+
+    let comment = repo.create_comment(post.id, auth.user_id, depth, comment.into_inner()).await?;
+    Ok(HttpResponse::Created().json(ApiResponse::success(CommentResponseDto::from(comment), "Comment created successfully")))
+}
+
+/// Depth of a new reply to `parent`, if `parent` can be replied to.
+fn reply_depth(parent: &comment::Model) -> Result<i32, AppError> {
+    if parent.deleted_at.is_some() || parent.hidden_at.is_some() {
+        return Err(AppError::conflict("Cannot reply to a deleted or hidden comment"));
     }
+    if parent.depth >= MAX_COMMENT_DEPTH {
+        return Err(AppError::unprocessable(format!(
+            "Replies cannot be nested more than {} levels deep",
+            MAX_COMMENT_DEPTH
+        )));
+    }
+    Ok(parent.depth + 1)
 }
 
-pub async fn create_post(db: web::Data<DatabaseConnection>, post: web::Json<post::Model>) -> impl Responder {
+pub async fn update_comment(
+    auth: AuthMiddleware,
+    db: web::Data<DatabaseConnection>,
+    id: web::Path<i32>,
+    comment: ValidatedJson<CommentUpdateDto>,
+) -> Result<HttpResponse, AppError> {
     let repo = Repository::new(db.get_ref().clone());
-    match repo.create_post(post.0).await {
-        Ok(post) => HttpResponse::Created().json(ApiResponse::success(post, "Post created successfully")),
-        Err(err) => HttpResponse::InternalServerError().json(
-            ApiResponse::<post::Model>::error(500, &format!("Error creating post: {}", err))
-        ),
+    let existing = find_comment(&repo, id.into_inner()).await?;
+    require_comment_author(&auth, &existing)?;
+    // Editing would let the author swap out content a moderator has hidden.
+    if existing.hidden_at.is_some() {
+        return Err(AppError::conflict("Hidden comments cannot be edited"));
     }
+
+    let comment = repo.update_comment(existing.id, comment.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(CommentResponseDto::from(comment), "Comment updated successfully")))
+}
+
+pub async fn delete_comment(auth: AuthMiddleware, db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let existing = find_comment(&repo, id.into_inner()).await?;
+    require_comment_author(&auth, &existing)?;
+
+    repo.delete_comment(existing.id).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::success((), "Comment deleted successfully")))
 }
 
-pub async fn get_post(db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> impl Responder {
+pub async fn hide_comment(_moderator: RequireRole<AdminOrModerator>, db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> Result<HttpResponse, AppError> {
     let repo = Repository::new(db.get_ref().clone());
-    match repo.find_post_by_id(id.into_inner()).await {
-        Ok(Some(post)) => HttpResponse::Ok().json(ApiResponse::success(post, "Post found")),
-        Ok(None) => HttpResponse::NotFound().json(
-            ApiResponse::<post::Model>::error(404, "Post not found")
-        ),
-        Err(err) => HttpResponse::InternalServerError().json(
-            ApiResponse::<post::Model>::error(500, &format!("Error retrieving post: {}", err))
-        ),
+    let existing = find_comment(&repo, id.into_inner()).await?;
+    let comment = repo.set_comment_hidden(existing.id, true).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(CommentResponseDto::from(comment), "Comment hidden successfully")))
+}
+
+pub async fn unhide_comment(_moderator: RequireRole<AdminOrModerator>, db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> Result<HttpResponse, AppError> {
+    let repo = Repository::new(db.get_ref().clone());
+    let existing = find_comment(&repo, id.into_inner()).await?;
+    let comment = repo.set_comment_hidden(existing.id, false).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(CommentResponseDto::from(comment), "Comment unhidden successfully")))
+}
+
+pub async fn get_comment_thread(
+    _access: ReadAccess,
+    auth: Result<AuthMiddleware, AppError>,
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    id: web::Path<i32>,
+    query: web::Query<CommentThreadQuery>,
+) -> Result<HttpResponse, AppError> {
+    let (request, include_hidden) = query.into_inner().into_parts()?;
+    let visibility = post_visibility(auth.as_ref().ok());
+    if include_hidden {
+        auth?.require_any_role(MODERATE_COMMENTS)?;
     }
+
+    let repo = Repository::new(db.get_ref().clone());
+    let post = find_visible_post(&repo, id.into_inner(), visibility).await?;
+    let page = repo.find_comment_thread(post.id, None, include_hidden, request).await?;
+    let meta = PageMeta::new(&req, &request, &page);
+    let thread = CommentNodeDto::forest(page.items, None);
+    Ok(HttpResponse::Ok().json(ApiResponse::success(thread, "Comments retrieved successfully").with_meta(meta)))
 }
 
-pub async fn update_post(
+pub async fn get_comment_replies(
+    _access: ReadAccess,
+    auth: Result<AuthMiddleware, AppError>,
+    req: HttpRequest,
     db: web::Data<DatabaseConnection>,
     id: web::Path<i32>,
-    post: web::Json<post::Model>,
-) -> impl Responder {
+    query: web::Query<CommentThreadQuery>,
+) -> Result<HttpResponse, AppError> {
+    let (request, include_hidden) = query.into_inner().into_parts()?;
+    let visibility = post_visibility(auth.as_ref().ok());
+    if include_hidden {
+        auth?.require_any_role(MODERATE_COMMENTS)?;
+    }
+
     let repo = Repository::new(db.get_ref().clone());
-    match repo.update_post(id.into_inner(), post.0).await {
-        Ok(post) => HttpResponse::Ok().json(ApiResponse::success(post, "Post updated successfully")),
-        Err(err) => HttpResponse::InternalServerError().json(
-            ApiResponse::<post::Model>::error(500, &format!("Error updating post: {}", err))
-        ),
+    let comment = repo
+        .find_comment_by_id(id.into_inner())
+        .await?
+        .filter(|comment| include_hidden || comment.hidden_at.is_none())
+        .ok_or_else(|| AppError::not_found("Comment not found"))?;
+    find_visible_post(&repo, comment.post_id, visibility)
+        .await
+        .map_err(|_| AppError::not_found("Comment not found"))?;
+    let page = repo.find_comment_thread(comment.post_id, Some(comment.id), include_hidden, request).await?;
+    let meta = PageMeta::new(&req, &request, &page);
+    let replies = CommentNodeDto::forest(page.items, Some(comment.id));
+    Ok(HttpResponse::Ok().json(ApiResponse::success(replies, "Replies retrieved successfully").with_meta(meta)))
+}
+
+/// A comment whose author account is gone can only be managed by `MANAGE_OTHERS`.
+fn require_comment_author(auth: &AuthMiddleware, comment: &comment::Model) -> Result<(), AppError> {
+    match comment.author_id {
+        Some(author_id) => auth.require_role_or_owner(MANAGE_OTHERS, author_id),
+        None => auth.require_any_role(MANAGE_OTHERS),
     }
 }
 
-pub async fn delete_post(db: web::Data<DatabaseConnection>, id: web::Path<i32>) -> impl Responder {
-    let repo = Repository::new(db.get_ref().clone());
-    match repo.delete_post(id.into_inner()).await {
-        Ok(_) => HttpResponse::Ok().json(ApiResponse::<()>::success((), "Post deleted successfully")),
-        Err(err) => HttpResponse::InternalServerError().json(
-            ApiResponse::<()>::error(500, &format!("Error deleting post: {}", err))
-        ),
+/// Deleted placeholders only exist to hold their thread together, so they
+/// cannot be edited or deleted again.
+async fn find_comment(repo: &Repository, id: i32) -> Result<comment::Model, AppError> {
+    repo.find_comment_by_id(id)
+        .await?
+        .filter(|comment| comment.deleted_at.is_none())
+        .ok_or_else(|| AppError::not_found("Comment not found"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    fn comment(depth: i32) -> comment::Model {
+        let at = DateTime::parse_from_rfc3339("2024-11-23T12:00:00+00:00").unwrap();
+        comment::Model {
+            id: 1,
+            post_id: 1,
+            author_id: Some(1),
+            content: "hello".to_string(),
+            hidden_at: None,
+            created_at: at,
+            updated_at: at,
+            parent_id: None,
+            depth,
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn replies_go_one_level_deeper() {
+        assert_eq!(reply_depth(&comment(0)).unwrap(), 1);
+        assert_eq!(reply_depth(&comment(MAX_COMMENT_DEPTH - 1)).unwrap(), MAX_COMMENT_DEPTH);
+    }
+
+    #[test]
+    fn replies_stop_at_the_depth_limit() {
+        assert!(matches!(reply_depth(&comment(MAX_COMMENT_DEPTH)), Err(AppError::Unprocessable(_))));
+    }
+
+    #[test]
+    fn deleted_and_hidden_comments_take_no_replies() {
+        let mut deleted = comment(0);
+        deleted.deleted_at = Some(deleted.created_at);
+        assert!(matches!(reply_depth(&deleted), Err(AppError::Conflict(_))));
+
+        let mut hidden = comment(0);
+        hidden.hidden_at = Some(hidden.created_at);
+        assert!(matches!(reply_depth(&hidden), Err(AppError::Conflict(_))));
     }
 }
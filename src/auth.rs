@@ -1,15 +1,32 @@
-use actix_web::{error::ErrorUnauthorized, web, Error as ActixError, FromRequest};
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use chrono::{Duration, Utc};
-use futures::future::{err, ok, Ready};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
-use serde::{Deserialize, Serialize};
+use futures::future::{ready, Ready};
+use jsonwebtoken::{decode, decode_header, encode, Header, Validation};
+use rand::{rngs::OsRng, RngCore};
+use sea_orm::SqlErr;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::net::IpAddr;
+use uuid::Uuid;
+use validator::Validate;
 
-use crate::dto::ApiResponse;
+use crate::dto::{ApiResponse, UserCreateDto};
 use crate::entities::user;
 use crate::entities::sea_orm_active_enums::UserRole;
+use crate::account;
+use crate::error::AppError;
+use crate::jwt;
+use crate::login_throttle::LoginThrottle;
+use crate::mailer::Mailer;
+use crate::two_factor::{self, ChallengePurpose, TwoFactorChallenge};
+use crate::password::{check_password_strength, dummy_verify, verify_password};
+use crate::repository::Repository;
+use crate::validation::ValidatedJson;
 
-const JWT_SECRET: &[u8] = b"your-secret-key"; // In production, use environment variable
-const TOKEN_EXPIRATION_HOURS: i64 = 24;
+const ACCESS_TOKEN_EXPIRATION_MINUTES: i64 = 15;
+const REFRESH_TOKEN_EXPIRATION_DAYS: i64 = 30;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginCredentials {
@@ -17,11 +34,27 @@ pub struct LoginCredentials {
     pub password: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct RegisterRequest {
+    #[validate(email, length(max = 255))]
+    pub email: String,
+    #[validate(length(min = 1, max = 100), custom(function = "crate::validation::not_blank"))]
+    pub first_name: String,
+    #[validate(length(min = 1, max = 100), custom(function = "crate::validation::not_blank"))]
+    pub last_name: String,
+    pub password: String,
+}
+
+/// `aud` of access tokens. 2FA challenges are signed with the same key but
+/// carry `two_factor::CHALLENGE_AUDIENCE`, so neither passes for the other.
+pub(crate) const ACCESS_TOKEN_AUDIENCE: &str = "access";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: i32, // user id
     pub email: String,
     pub role: String,
+    pub aud: String,
     pub exp: i64,
 }
 
@@ -29,6 +62,22 @@ pub struct Claims {
 pub struct AuthResponse {
     pub token: String,
     pub token_type: String,
+    pub expires_in: i64,
+    pub refresh_token: String,
+}
+
+/// What `login` hands back: either the token pair, or a 2FA challenge that
+/// has to be completed through `/auth/login/2fa` (or enrollment) first.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Tokens(AuthResponse),
+    TwoFactor(TwoFactorChallenge),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
 }
 
 pub struct AuthMiddleware {
@@ -37,43 +86,103 @@ pub struct AuthMiddleware {
     pub role: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct AuthSettings {
+    pub protect_reads: bool,
+    pub require_verified_email: bool,
+    pub require_admin_totp: bool,
+    pub totp_issuer: String,
+    pub app_base_url: String,
+}
+
+impl AuthSettings {
+    pub fn from_env() -> Self {
+        let protect_reads = env_flag("AUTH_PROTECT_READS");
+        let require_verified_email = env_flag("REQUIRE_EMAIL_VERIFICATION");
+        let require_admin_totp = env_flag("REQUIRE_ADMIN_TOTP");
+        let totp_issuer = env::var("TOTP_ISSUER").unwrap_or_else(|_| "rust-postgres-server".to_string());
+        let app_base_url = env::var("APP_BASE_URL").unwrap_or_else(|_| "http://localhost:3333".to_string());
+
+        Self { protect_reads, require_verified_email, require_admin_totp, totp_issuer, app_base_url }
+    }
+}
+
+fn env_flag(name: &str) -> bool {
+    env::var(name)
+        .map(|value| matches!(value.to_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+pub(crate) fn bearer_token(req: &actix_web::HttpRequest) -> Result<&str, AppError> {
+    let auth_header = match req.headers().get("Authorization") {
+        Some(header) => header,
+        None => return Err(AppError::unauthorized("No authorization header")),
+    };
+
+    let auth_str = match auth_header.to_str() {
+        Ok(str) => str,
+        Err(_) => return Err(AppError::unauthorized("Invalid authorization header")),
+    };
+
+    match auth_str.strip_prefix("Bearer ") {
+        Some(token) => Ok(token),
+        None => Err(AppError::unauthorized("Invalid authorization header format")),
+    }
+}
+
+fn authenticate(req: &actix_web::HttpRequest) -> Result<AuthMiddleware, AppError> {
+    let token = bearer_token(req)?;
+
+    match validate_token(token) {
+        Ok(claims) => Ok(AuthMiddleware {
+            user_id: claims.sub,
+            email: claims.email,
+            role: claims.role,
+        }),
+        Err(_) => Err(AppError::unauthorized("Invalid token")),
+    }
+}
+
 impl FromRequest for AuthMiddleware {
-    type Error = ActixError;
+    type Error = AppError;
     type Future = Ready<Result<Self, Self::Error>>;
 
     fn from_request(
         req: &actix_web::HttpRequest,
         _payload: &mut actix_web::dev::Payload,
     ) -> Self::Future {
-        let auth_header = match req.headers().get("Authorization") {
-            Some(header) => header,
-            None => return err(ErrorUnauthorized("No authorization header")),
-        };
-
-        let auth_str = match auth_header.to_str() {
-            Ok(str) => str,
-            Err(_) => return err(ErrorUnauthorized("Invalid authorization header")),
-        };
-
-        if !auth_str.starts_with("Bearer ") {
-            return err(ErrorUnauthorized("Invalid authorization header format"));
-        }
+        ready(authenticate(req))
+    }
+}
+
+/// Extractor for read-only routes: requires a valid token only when
+/// `AuthSettings::protect_reads` is enabled, otherwise lets anonymous callers through.
+pub struct ReadAccess(pub Option<AuthMiddleware>);
 
-        let token = &auth_str[7..];
-        match validate_token(token) {
-            Ok(claims) => ok(AuthMiddleware {
-                user_id: claims.sub,
-                email: claims.email,
-                role: claims.role,
-            }),
-            Err(_) => err(ErrorUnauthorized("Invalid token")),
+impl FromRequest for ReadAccess {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        _payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let protect_reads = req
+            .app_data::<web::Data<AuthSettings>>()
+            .map(|settings| settings.protect_reads)
+            .unwrap_or(false);
+
+        if !protect_reads {
+            return ready(Ok(ReadAccess(None)));
         }
+
+        ready(authenticate(req).map(|auth| ReadAccess(Some(auth))))
     }
 }
 
 pub fn generate_token(user: &user::Model) -> Result<String, jsonwebtoken::errors::Error> {
     let expiration = Utc::now()
-        .checked_add_signed(Duration::hours(TOKEN_EXPIRATION_HOURS))
+        .checked_add_signed(Duration::minutes(ACCESS_TOKEN_EXPIRATION_MINUTES))
         .expect("valid timestamp")
         .timestamp();
 
@@ -85,48 +194,328 @@ pub fn generate_token(user: &user::Model) -> Result<String, jsonwebtoken::errors
             UserRole::User => "User",
             UserRole::Moderator => "Moderator",
         }.to_string(),
+        aud: ACCESS_TOKEN_AUDIENCE.to_string(),
         exp: expiration,
     };
 
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET),
-    )
+    sign_claims(&claims)
 }
 
 pub fn validate_token(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET),
-        &Validation::default(),
-    )?;
+    verify_claims(token, ACCESS_TOKEN_AUDIENCE)
+}
+
+/// Signs any claim set with the current key. Access tokens and the short-lived
+/// 2FA challenge tokens share this, and are told apart by their `aud` claim.
+pub(crate) fn sign_claims<T: Serialize>(claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+    let keys = jwt::keys();
+    let mut header = Header::new(keys.algorithm);
+    header.kid = Some(keys.signing_kid.clone());
+
+    encode(&header, claims, keys.encoding_key())
+}
+
+/// Checks the signature, expiry and that `aud` is exactly `audience`.
+pub(crate) fn verify_claims<T: DeserializeOwned>(token: &str, audience: &str) -> Result<T, jsonwebtoken::errors::Error> {
+    let keys = jwt::keys();
+    let header = decode_header(token)?;
+    let decoding_key = keys
+        .decoding_key(header.kid.as_deref())
+        .ok_or(jsonwebtoken::errors::ErrorKind::InvalidSignature)?;
+
+    // Only the configured algorithm is accepted, whatever the header claims.
+    let mut validation = Validation::new(keys.algorithm);
+    validation.set_audience(&[audience]);
+    let token_data = decode::<T>(token, decoding_key, &validation)?;
     Ok(token_data.claims)
 }
 
+/// Every way a login can fail ends here, so the response never tells an unknown
+/// email, a wrong password and a locked account apart.
+pub(crate) fn invalid_credentials() -> AppError {
+    AppError::unauthorized("Invalid credentials")
+}
+
+pub(crate) fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    req.peer_addr().map(|addr| addr.ip())
+}
+
+pub(crate) fn is_locked(user: &user::Model) -> bool {
+    user.locked_until.is_some_and(|until| until > Utc::now())
+}
+
+/// Counts a failed attempt against the client address and, when known, the account.
+/// Each failure pushes the account's `locked_until` out exponentially until it hits
+/// the full lockout.
+pub(crate) async fn record_login_failure(
+    repo: &Repository,
+    throttle: &LoginThrottle,
+    user: Option<&user::Model>,
+    ip: Option<IpAddr>,
+) -> Result<(), AppError> {
+    if let Some(ip) = ip {
+        throttle.record_failure(ip);
+    }
+
+    if let Some(user) = user {
+        let failures = repo.increment_failed_logins(user.id).await?;
+        let delay = throttle.policy.delay_after(failures, throttle.policy.max_failures);
+        repo.lock_user_until(user.id, Utc::now() + delay).await?;
+        if failures >= throttle.policy.max_failures {
+            log::warn!("User {} locked after {} failed login attempts", user.id, failures);
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn record_login_success(
+    repo: &Repository,
+    throttle: &LoginThrottle,
+    user: &user::Model,
+    ip: Option<IpAddr>,
+) -> Result<(), AppError> {
+    if let Some(ip) = ip {
+        throttle.record_success(ip);
+    }
+
+    if user.failed_login_attempts > 0 || user.locked_until.is_some() {
+        repo.reset_failed_logins(user.id).await?;
+    }
+
+    Ok(())
+}
+
 pub async fn login(
+    req: HttpRequest,
     credentials: web::Json<LoginCredentials>,
-    repo: web::Data<crate::repository::Repository>,
-) -> Result<web::Json<ApiResponse<AuthResponse>>, ActixError> {
-    // In a real application, you would hash the password and compare with stored hash
-    let user = match repo
-        .find_user_by_email(&credentials.email)
-        .await
-        .map_err(|_| ErrorUnauthorized("Invalid credentials"))?
-    {
+    repo: web::Data<Repository>,
+    settings: web::Data<AuthSettings>,
+    throttle: web::Data<LoginThrottle>,
+) -> Result<web::Json<ApiResponse<LoginResponse>>, AppError> {
+    let ip = client_ip(&req);
+    if ip.is_some_and(|ip| throttle.is_blocked(ip)) {
+        dummy_verify(&credentials.password);
+        return Err(invalid_credentials());
+    }
+
+    let user = match repo.find_user_by_email(&credentials.email).await? {
         Some(user) => user,
-        None => return Err(ErrorUnauthorized("Invalid credentials")),
+        None => {
+            dummy_verify(&credentials.password);
+            record_login_failure(&repo, &throttle, None, ip).await?;
+            return Err(invalid_credentials());
+        }
     };
 
-    // TODO: Add proper password verification here
-    // For now, we're just generating a token without password check
-    let token = generate_token(&user).map_err(|_| ErrorUnauthorized("Token generation failed"))?;
+    // A locked account is not even checked, so guessing cannot continue while it is locked.
+    if is_locked(&user) {
+        dummy_verify(&credentials.password);
+        record_login_failure(&repo, &throttle, None, ip).await?;
+        return Err(invalid_credentials());
+    }
+
+    let password_matches = match &user.password_hash {
+        Some(hash) => verify_password(&credentials.password, hash),
+        None => {
+            dummy_verify(&credentials.password);
+            false
+        }
+    };
+    if !password_matches {
+        record_login_failure(&repo, &throttle, Some(&user), ip).await?;
+        return Err(invalid_credentials());
+    }
+    require_verified(&settings, &user)?;
+
+    // With 2FA the counters are only reset once the second factor has been checked too,
+    // otherwise knowing the password would allow unlimited code guesses.
+    if user.totp_enabled_at.is_some() {
+        let challenge = two_factor::issue_challenge(&user, ChallengePurpose::Verify)?;
+        return Ok(web::Json(ApiResponse::success(
+            LoginResponse::TwoFactor(challenge),
+            "Two-factor authentication required",
+        )));
+    }
+
+    record_login_success(&repo, &throttle, &user, ip).await?;
+
+    if settings.require_admin_totp && user.user_role == UserRole::Admin {
+        let challenge = two_factor::issue_challenge(&user, ChallengePurpose::Enroll)?;
+        return Ok(web::Json(ApiResponse::success(
+            LoginResponse::TwoFactor(challenge),
+            "Two-factor enrollment required for admin accounts",
+        )));
+    }
+
+    let response = issue_token_pair(&repo, &user).await?;
+
+    Ok(web::Json(ApiResponse::success(LoginResponse::Tokens(response), "Login successful")))
+}
+
+/// Refuses sessions for unverified accounts when `REQUIRE_EMAIL_VERIFICATION` is on.
+fn require_verified(settings: &AuthSettings, user: &user::Model) -> Result<(), AppError> {
+    if settings.require_verified_email && user.verified_at.is_none() {
+        return Err(AppError::forbidden("Email address has not been verified"));
+    }
+    Ok(())
+}
+
+/// Self-service sign-up. Always creates a plain `UserRole::User`; roles are
+/// only handed out by admins through `/api/users`.
+pub async fn register(
+    body: ValidatedJson<RegisterRequest>,
+    repo: web::Data<Repository>,
+    mailer: web::Data<dyn Mailer>,
+    settings: web::Data<AuthSettings>,
+) -> Result<HttpResponse, AppError> {
+    let body = body.into_inner();
+    let email = body.email.trim().to_string();
+    check_password_strength(&body.password, &email).map_err(AppError::Validation)?;
+
+    let existing = repo.find_user_by_email(&email).await?;
+    if existing.is_some() {
+        return Err(AppError::conflict("Email is already registered"));
+    }
+
+    let user = repo
+        .create_user(UserCreateDto {
+            email,
+            first_name: body.first_name,
+            last_name: body.last_name,
+            user_role: UserRole::User,
+            password: body.password,
+        })
+        .await
+        .map_err(|err| match err.sql_err() {
+            // Lost a race against a concurrent registration with the same email.
+            Some(SqlErr::UniqueConstraintViolation(_)) => AppError::conflict("Email is already registered"),
+            _ => AppError::from(err),
+        })?;
+
+    // The account exists at this point; a mail hiccup must not fail the sign-up,
+    // the user can ask for a new link through /auth/verify-email/request.
+    if let Err(err) = account::send_verification_email(&repo, mailer.get_ref(), &settings, &user).await {
+        log::warn!("Failed to send verification email to user {}: {}", user.id, err);
+    }
+
+    // Same rule as `login`: no session until the address is confirmed.
+    if settings.require_verified_email {
+        return Ok(HttpResponse::Created().json(ApiResponse::success(
+            (),
+            "Registration successful; verify your email address to log in",
+        )));
+    }
+
+    let response = issue_token_pair(&repo, &user).await?;
+
+    Ok(HttpResponse::Created().json(ApiResponse::success(response, "Registration successful")))
+}
+
+// Refresh, verification and reset tokens are opaque random strings; only their
+// SHA-256 digest is stored.
+pub(crate) fn generate_opaque_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub(crate) fn hash_opaque_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+fn refresh_token_expiry() -> chrono::DateTime<Utc> {
+    Utc::now() + Duration::days(REFRESH_TOKEN_EXPIRATION_DAYS)
+}
+
+fn auth_response(user: &user::Model, refresh_token: String) -> Result<AuthResponse, AppError> {
+    let token = generate_token(user).map_err(|err| AppError::internal(format!("Token generation failed: {}", err)))?;
+
+    Ok(AuthResponse {
+        token,
+        token_type: "Bearer".to_string(),
+        expires_in: ACCESS_TOKEN_EXPIRATION_MINUTES * 60,
+        refresh_token,
+    })
+}
+
+/// Starts a new session (refresh token family) for `user`.
+pub async fn issue_token_pair(repo: &Repository, user: &user::Model) -> Result<AuthResponse, AppError> {
+    let refresh_token = generate_opaque_token();
+    repo.create_refresh_token(
+        user.id,
+        Uuid::new_v4(),
+        hash_opaque_token(&refresh_token),
+        refresh_token_expiry(),
+    )
+    .await?;
+
+    auth_response(user, refresh_token)
+}
+
+pub async fn refresh(
+    body: web::Json<RefreshRequest>,
+    repo: web::Data<Repository>,
+    settings: web::Data<AuthSettings>,
+) -> Result<web::Json<ApiResponse<AuthResponse>>, AppError> {
+    let current = repo
+        .find_refresh_token_by_hash(&hash_opaque_token(&body.refresh_token))
+        .await?
+        .ok_or_else(|| AppError::unauthorized("Invalid refresh token"))?;
+
+    // A revoked token being presented again means it was copied; kill the whole session.
+    if current.revoked_at.is_some() {
+        repo.revoke_refresh_token_family(current.family_id).await?;
+        return Err(AppError::unauthorized("Invalid refresh token"));
+    }
+    if current.expires_at < Utc::now() {
+        return Err(AppError::unauthorized("Refresh token expired"));
+    }
+
+    let user = repo
+        .find_user_by_id(current.user_id)
+        .await?
+        .ok_or_else(|| AppError::unauthorized("Invalid refresh token"))?;
+    // Sessions opened before REQUIRE_ADMIN_TOTP was switched on must go through enrollment.
+    if settings.require_admin_totp && user.user_role == UserRole::Admin && user.totp_enabled_at.is_none() {
+        return Err(AppError::unauthorized("Two-factor enrollment required"));
+    }
+    require_verified(&settings, &user)?;
+
+    let refresh_token = generate_opaque_token();
+    let rotated = repo.rotate_refresh_token(&current, hash_opaque_token(&refresh_token), refresh_token_expiry()).await?;
+    if rotated.is_none() {
+        repo.revoke_refresh_token_family(current.family_id).await?;
+        return Err(AppError::unauthorized("Invalid refresh token"));
+    }
 
     Ok(web::Json(ApiResponse::success(
-        AuthResponse {
-            token,
-            token_type: "Bearer".to_string(),
-        },
-        "Login successful",
+        auth_response(&user, refresh_token)?,
+        "Token refreshed",
     )))
 }
+
+/// Ends the session the refresh token belongs to. Unknown tokens are ignored.
+pub async fn logout(
+    body: web::Json<RefreshRequest>,
+    repo: web::Data<Repository>,
+) -> Result<web::Json<ApiResponse<()>>, AppError> {
+    let token = repo.find_refresh_token_by_hash(&hash_opaque_token(&body.refresh_token)).await?;
+
+    if let Some(token) = token {
+        repo.revoke_refresh_token_family(token.family_id).await?;
+    }
+
+    Ok(web::Json(ApiResponse::success((), "Logged out")))
+}
+
+/// Revokes every session of the caller. Access tokens already issued stay valid
+/// until they expire, which is why they are kept short-lived.
+pub async fn logout_all(
+    auth: AuthMiddleware,
+    repo: web::Data<Repository>,
+) -> Result<web::Json<ApiResponse<()>>, AppError> {
+    repo.revoke_user_refresh_tokens(auth.user_id).await?;
+
+    Ok(web::Json(ApiResponse::success((), "Logged out of all sessions")))
+}
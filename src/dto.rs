@@ -1,20 +1,675 @@
-use serde::{Deserialize, Serialize};
-use crate::entities::sea_orm_active_enums::UserRole;
+use std::collections::HashMap;
 
-#[derive(Debug, Serialize, Deserialize)]
+use chrono::{DateTime, Utc};
+use sea_orm::{ActiveValue::{self, NotSet, Set}, prelude::DateTimeWithTimeZone, DbErr, FromQueryResult, QueryResult, TryGetable};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use validator::Validate;
+use crate::entities::{user, profile, post, category, comment};
+use crate::entities::sea_orm_active_enums::{PostStatus, UserRole};
+use crate::pagination::PageMeta;
+use crate::repository::{CommentThreadRow, PostSearchRow};
+
+// Request bodies. They only carry what a client may set: ids, owners and
+// timestamps come from the path, the token or the server.
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
 pub struct UserCreateDto {
+    #[validate(email, length(max = 255))]
+    pub email: String,
+    #[validate(length(min = 1, max = 100), custom(function = "crate::validation::not_blank"))]
+    pub first_name: String,
+    #[validate(length(min = 1, max = 100), custom(function = "crate::validation::not_blank"))]
+    pub last_name: String,
+    pub user_role: UserRole,
+    #[validate(length(min = 10, max = 128))]
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct UserUpdateDto {
+    #[validate(email, length(max = 255))]
     pub email: String,
+    #[validate(length(min = 1, max = 100), custom(function = "crate::validation::not_blank"))]
     pub first_name: String,
+    #[validate(length(min = 1, max = 100), custom(function = "crate::validation::not_blank"))]
     pub last_name: String,
     pub user_role: UserRole,
 }
 
+impl UserUpdateDto {
+    pub fn into_active_model(self, id: i32) -> user::ActiveModel {
+        user::ActiveModel {
+            id: Set(id),
+            email: Set(self.email),
+            first_name: Set(self.first_name),
+            last_name: Set(self.last_name),
+            user_role: Set(self.user_role),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct ProfileCreateDto {
+    #[validate(length(max = 2000))]
+    pub bio: Option<String>,
+    #[validate(url, length(max = 2048))]
+    pub avatar: Option<String>,
+    #[validate(custom(function = "crate::validation::phone_number"))]
+    pub phone_number: Option<String>,
+    #[validate(custom(function = "crate::validation::birth_date"))]
+    pub birth_date: Option<DateTime<Utc>>,
+}
+
+impl ProfileCreateDto {
+    pub fn into_active_model(self, user_id: i32) -> profile::ActiveModel {
+        profile::ActiveModel {
+            user_id: Set(user_id),
+            bio: Set(self.bio),
+            avatar: Set(self.avatar),
+            phone_number: Set(self.phone_number),
+            birth_date: Set(self.birth_date),
+            ..Default::default()
+        }
+    }
+}
+
+/// Replaces every editable field; omitted optional fields are cleared.
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct ProfileUpdateDto {
+    #[validate(length(max = 2000))]
+    pub bio: Option<String>,
+    #[validate(url, length(max = 2048))]
+    pub avatar: Option<String>,
+    #[validate(custom(function = "crate::validation::phone_number"))]
+    pub phone_number: Option<String>,
+    #[validate(custom(function = "crate::validation::birth_date"))]
+    pub birth_date: Option<DateTime<Utc>>,
+}
+
+impl ProfileUpdateDto {
+    pub fn into_active_model(self, id: i32) -> profile::ActiveModel {
+        profile::ActiveModel {
+            id: Set(id),
+            bio: Set(self.bio),
+            avatar: Set(self.avatar),
+            phone_number: Set(self.phone_number),
+            birth_date: Set(self.birth_date),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct PostCreateDto {
+    #[validate(length(min = 1, max = 255), custom(function = "crate::validation::not_blank"))]
+    pub title: String,
+    #[validate(length(min = 1, max = 100000))]
+    pub content: String,
+}
+
+impl PostCreateDto {
+    pub fn into_active_model(self, author_id: i32) -> post::ActiveModel {
+        post::ActiveModel {
+            author_id: Set(author_id),
+            title: Set(self.title),
+            content: Set(self.content),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct PostUpdateDto {
+    #[validate(length(min = 1, max = 255), custom(function = "crate::validation::not_blank"))]
+    pub title: String,
+    #[validate(length(min = 1, max = 100000))]
+    pub content: String,
+}
+
+impl PostUpdateDto {
+    pub fn into_active_model(self, id: i32) -> post::ActiveModel {
+        post::ActiveModel {
+            id: Set(id),
+            title: Set(self.title),
+            content: Set(self.content),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CategoryCreateDto {
+    #[validate(length(min = 1, max = 100), custom(function = "crate::validation::not_blank"))]
+    pub category_name: String,
+    #[validate(length(max = 2000))]
+    pub description: Option<String>,
+}
+
+impl CategoryCreateDto {
+    pub fn into_active_model(self) -> category::ActiveModel {
+        category::ActiveModel {
+            category_name: Set(self.category_name),
+            description: Set(self.description),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CategoryUpdateDto {
+    #[validate(length(min = 1, max = 100), custom(function = "crate::validation::not_blank"))]
+    pub category_name: String,
+    #[validate(length(max = 2000))]
+    pub description: Option<String>,
+}
+
+impl CategoryUpdateDto {
+    pub fn into_active_model(self, id: i32) -> category::ActiveModel {
+        category::ActiveModel {
+            id: Set(id),
+            category_name: Set(self.category_name),
+            description: Set(self.description),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CommentCreateDto {
+    #[validate(length(min = 1, max = 10000), custom(function = "crate::validation::not_blank"))]
+    pub content: String,
+    /// The comment being replied to, on the same post. Top-level when missing.
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+}
+
+impl CommentCreateDto {
+    pub fn into_active_model(self, post_id: i32, author_id: i32, depth: i32) -> comment::ActiveModel {
+        comment::ActiveModel {
+            post_id: Set(post_id),
+            author_id: Set(Some(author_id)),
+            content: Set(self.content),
+            parent_id: Set(self.parent_id),
+            depth: Set(depth),
+            ..Default::default()
+        }
+    }
+}
+
+/// `POST /api/posts/{id}/schedule`.
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct PostScheduleDto {
+    #[validate(custom(function = "crate::validation::in_future"))]
+    pub scheduled_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct CommentUpdateDto {
+    #[validate(length(min = 1, max = 10000), custom(function = "crate::validation::not_blank"))]
+    pub content: String,
+}
+
+impl CommentUpdateDto {
+    pub fn into_active_model(self, id: i32) -> comment::ActiveModel {
+        comment::ActiveModel {
+            id: Set(id),
+            content: Set(self.content),
+            ..Default::default()
+        }
+    }
+}
+
+// Partial updates (PATCH, also accepted as `application/merge-patch+json`).
+// Following RFC 7396, a missing member leaves the column alone and `null`
+// clears it. Required columns cannot be cleared, so `null` is rejected there.
+
+/// `None` when the member is missing, `Some(None)` for an explicit `null`.
+fn nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+fn non_null<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer)?
+        .map(Some)
+        .ok_or_else(|| D::Error::custom("this field cannot be cleared with null"))
+}
+
+fn set_if_present<T: Into<sea_orm::Value>>(value: Option<T>) -> ActiveValue<T> {
+    match value {
+        Some(value) => Set(value),
+        None => NotSet,
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Validate)]
+pub struct UserPatchDto {
+    #[serde(default, deserialize_with = "non_null")]
+    #[validate(email, length(max = 255))]
+    pub email: Option<String>,
+    #[serde(default, deserialize_with = "non_null")]
+    #[validate(length(min = 1, max = 100), custom(function = "crate::validation::not_blank"))]
+    pub first_name: Option<String>,
+    #[serde(default, deserialize_with = "non_null")]
+    #[validate(length(min = 1, max = 100), custom(function = "crate::validation::not_blank"))]
+    pub last_name: Option<String>,
+    #[serde(default, deserialize_with = "non_null")]
+    pub user_role: Option<UserRole>,
+}
+
+impl UserPatchDto {
+    pub fn into_active_model(self, id: i32) -> user::ActiveModel {
+        user::ActiveModel {
+            id: Set(id),
+            email: set_if_present(self.email),
+            first_name: set_if_present(self.first_name),
+            last_name: set_if_present(self.last_name),
+            user_role: set_if_present(self.user_role),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Validate)]
+pub struct ProfilePatchDto {
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(length(max = 2000))]
+    pub bio: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(url, length(max = 2048))]
+    pub avatar: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(custom(function = "crate::validation::phone_number"))]
+    pub phone_number: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(custom(function = "crate::validation::birth_date"))]
+    pub birth_date: Option<Option<DateTime<Utc>>>,
+}
+
+impl ProfilePatchDto {
+    pub fn into_active_model(self, id: i32) -> profile::ActiveModel {
+        profile::ActiveModel {
+            id: Set(id),
+            bio: set_if_present(self.bio),
+            avatar: set_if_present(self.avatar),
+            phone_number: set_if_present(self.phone_number),
+            birth_date: set_if_present(self.birth_date),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Validate)]
+pub struct PostPatchDto {
+    #[serde(default, deserialize_with = "non_null")]
+    #[validate(length(min = 1, max = 255), custom(function = "crate::validation::not_blank"))]
+    pub title: Option<String>,
+    #[serde(default, deserialize_with = "non_null")]
+    #[validate(length(min = 1, max = 100000))]
+    pub content: Option<String>,
+}
+
+impl PostPatchDto {
+    pub fn into_active_model(self, id: i32) -> post::ActiveModel {
+        post::ActiveModel {
+            id: Set(id),
+            title: set_if_present(self.title),
+            content: set_if_present(self.content),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Validate)]
+pub struct CategoryPatchDto {
+    #[serde(default, deserialize_with = "non_null")]
+    #[validate(length(min = 1, max = 100), custom(function = "crate::validation::not_blank"))]
+    pub category_name: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(length(max = 2000))]
+    pub description: Option<Option<String>>,
+}
+
+impl CategoryPatchDto {
+    pub fn into_active_model(self, id: i32) -> category::ActiveModel {
+        category::ActiveModel {
+            id: Set(id),
+            category_name: set_if_present(self.category_name),
+            description: set_if_present(self.description),
+            ..Default::default()
+        }
+    }
+}
+
+// Response bodies. Internal columns (password and 2FA secrets, lockout
+// counters) never leave the server.
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UserResponseDto {
+    pub id: i32,
+    pub email: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub user_role: UserRole,
+    pub verified_at: Option<DateTime<Utc>>,
+    pub two_factor_enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Only present with `?include=profile`; `null` when the user has none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<Option<ProfileResponseDto>>,
+    /// Only present with `?include=posts`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub posts: Option<Vec<PostResponseDto>>,
+}
+
+impl From<user::Model> for UserResponseDto {
+    fn from(user: user::Model) -> Self {
+        Self {
+            id: user.id,
+            email: user.email,
+            first_name: user.first_name,
+            last_name: user.last_name,
+            user_role: user.user_role,
+            verified_at: user.verified_at,
+            two_factor_enabled: user.totp_enabled_at.is_some(),
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+            profile: None,
+            posts: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileResponseDto {
+    pub id: i32,
+    pub user_id: i32,
+    pub bio: Option<String>,
+    pub avatar: Option<String>,
+    pub phone_number: Option<String>,
+    pub birth_date: Option<DateTime<Utc>>,
+}
+
+impl From<profile::Model> for ProfileResponseDto {
+    fn from(profile: profile::Model) -> Self {
+        Self {
+            id: profile.id,
+            user_id: profile.user_id,
+            bio: profile.bio,
+            avatar: profile.avatar,
+            phone_number: profile.phone_number,
+            birth_date: profile.birth_date,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PostResponseDto {
+    pub id: i32,
+    pub title: String,
+    pub content: String,
+    pub post_status: PostStatus,
+    pub published_at: Option<DateTimeWithTimeZone>,
+    pub scheduled_at: Option<DateTimeWithTimeZone>,
+    pub author_id: i32,
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+    /// Visible comments only; hidden ones are not counted.
+    pub comment_count: u64,
+    /// Only present with `?include=author`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<UserResponseDto>,
+}
+
+impl From<post::Model> for PostResponseDto {
+    fn from(post: post::Model) -> Self {
+        Self {
+            id: post.id,
+            title: post.title,
+            content: post.content,
+            post_status: post.post_status,
+            published_at: post.published_at,
+            scheduled_at: post.scheduled_at,
+            author_id: post.author_id,
+            created_at: post.created_at,
+            updated_at: post.updated_at,
+            comment_count: 0,
+            author: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryResponseDto {
+    pub id: i32,
+    pub category_name: String,
+    pub description: Option<String>,
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+impl From<category::Model> for CategoryResponseDto {
+    fn from(category: category::Model) -> Self {
+        Self {
+            id: category.id,
+            category_name: category.category_name,
+            description: category.description,
+            created_at: category.created_at,
+            updated_at: category.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommentResponseDto {
+    pub id: i32,
+    pub post_id: i32,
+    pub parent_id: Option<i32>,
+    pub depth: i32,
+    /// `null` once the comment is deleted.
+    pub author_id: Option<i32>,
+    /// `"[deleted]"` for a deleted comment that is kept because it has replies.
+    pub content: String,
+    /// Set when a moderator hid the comment; hidden comments are only listed for moderators.
+    pub hidden_at: Option<DateTimeWithTimeZone>,
+    pub deleted_at: Option<DateTimeWithTimeZone>,
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+pub const DELETED_COMMENT_PLACEHOLDER: &str = "[deleted]";
+
+impl From<comment::Model> for CommentResponseDto {
+    fn from(comment: comment::Model) -> Self {
+        let deleted = comment.deleted_at.is_some();
+        Self {
+            id: comment.id,
+            post_id: comment.post_id,
+            parent_id: comment.parent_id,
+            depth: comment.depth,
+            author_id: comment.author_id.filter(|_| !deleted),
+            content: if deleted { DELETED_COMMENT_PLACEHOLDER.to_string() } else { comment.content },
+            hidden_at: comment.hidden_at,
+            deleted_at: comment.deleted_at,
+            created_at: comment.created_at,
+            updated_at: comment.updated_at,
+        }
+    }
+}
+
+/// One comment of a thread with the first page of its replies. `reply_count`
+/// counts every direct reply, so clients can tell when to load more.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommentNodeDto {
+    #[serde(flatten)]
+    pub comment: CommentResponseDto,
+    pub reply_count: u64,
+    pub replies: Vec<CommentNodeDto>,
+}
+
+impl CommentNodeDto {
+    /// Nests the flat rows of a thread query under their parents. `root` is
+    /// the parent of the top level: `None` for a whole post, or a comment id.
+    pub fn forest(rows: Vec<CommentThreadRow>, root: Option<i32>) -> Vec<CommentNodeDto> {
+        let mut children: HashMap<Option<i32>, Vec<CommentThreadRow>> = HashMap::new();
+        for row in rows {
+            children.entry(row.comment.parent_id).or_default().push(row);
+        }
+        Self::nest(&mut children, root)
+    }
+
+    fn nest(children: &mut HashMap<Option<i32>, Vec<CommentThreadRow>>, parent: Option<i32>) -> Vec<CommentNodeDto> {
+        children
+            .remove(&parent)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|row| CommentNodeDto {
+                replies: Self::nest(children, Some(row.comment.id)),
+                reply_count: row.reply_count as u64,
+                comment: CommentResponseDto::from(row.comment),
+            })
+            .collect()
+    }
+}
+
+// Rows narrowed by `?fields=`. Only the id is always selected; any other
+// column that was left out of the query is left out of the JSON as well.
+
+/// Reads a column that may not be part of the query.
+fn selected<T: TryGetable>(res: &QueryResult, pre: &str, column: &str) -> Option<T> {
+    res.try_get(pre, column).ok()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UserFieldsDto {
+    pub id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_role: Option<UserRole>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verified_at: Option<Option<DateTime<Utc>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub two_factor_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl FromQueryResult for UserFieldsDto {
+    fn from_query_result(res: &QueryResult, pre: &str) -> Result<Self, DbErr> {
+        Ok(Self {
+            id: res.try_get(pre, "id")?,
+            email: selected(res, pre, "email"),
+            first_name: selected(res, pre, "first_name"),
+            last_name: selected(res, pre, "last_name"),
+            user_role: selected(res, pre, "user_role"),
+            verified_at: selected(res, pre, "verified_at"),
+            two_factor_enabled: selected::<Option<DateTime<Utc>>>(res, pre, "totp_enabled_at").map(|at| at.is_some()),
+            created_at: selected(res, pre, "created_at"),
+            updated_at: selected(res, pre, "updated_at"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PostFieldsDto {
+    pub id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_status: Option<PostStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub published_at: Option<Option<DateTimeWithTimeZone>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheduled_at: Option<Option<DateTimeWithTimeZone>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTimeWithTimeZone>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTimeWithTimeZone>,
+}
+
+impl FromQueryResult for PostFieldsDto {
+    fn from_query_result(res: &QueryResult, pre: &str) -> Result<Self, DbErr> {
+        Ok(Self {
+            id: res.try_get(pre, "id")?,
+            title: selected(res, pre, "title"),
+            content: selected(res, pre, "content"),
+            post_status: selected(res, pre, "post_status"),
+            published_at: selected(res, pre, "published_at"),
+            scheduled_at: selected(res, pre, "scheduled_at"),
+            author_id: selected(res, pre, "author_id"),
+            created_at: selected(res, pre, "created_at"),
+            updated_at: selected(res, pre, "updated_at"),
+        })
+    }
+}
+
+/// A search hit: the post plus its rank and highlighted fragments. The
+/// highlights are plain text with matches wrapped in `<mark>`; they are not
+/// HTML-escaped.
+#[derive(Debug, Serialize)]
+pub struct PostSearchResultDto {
+    #[serde(flatten)]
+    pub post: PostResponseDto,
+    pub rank: f32,
+    pub title_highlight: String,
+    pub snippet: String,
+}
+
+impl From<PostSearchRow> for PostSearchResultDto {
+    fn from(row: PostSearchRow) -> Self {
+        Self {
+            post: PostResponseDto {
+                id: row.id,
+                title: row.title,
+                content: row.content,
+                post_status: row.post_status,
+                published_at: row.published_at,
+                scheduled_at: row.scheduled_at,
+                author_id: row.author_id,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+                comment_count: 0,
+                author: None,
+            },
+            rank: row.rank,
+            title_highlight: row.title_highlight,
+            snippet: row.snippet,
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct ApiResponse<T> {
     pub status: String,
     pub code: u16,
     pub message: String,
     pub data: Option<T>,
+    /// Paging details on list responses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<PageMeta>,
+    /// Set on errors; the same id is written to the server log.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_id: Option<String>,
 }
 
 impl<T> ApiResponse<T> {
@@ -24,6 +679,8 @@ impl<T> ApiResponse<T> {
             code: 200,
             message: message.to_string(),
             data: Some(data),
+            meta: None,
+            error_id: None,
         }
     }
 
@@ -33,6 +690,91 @@ impl<T> ApiResponse<T> {
             code,
             message: message.to_string(),
             data: None,
+            meta: None,
+            error_id: None,
         }
     }
+
+    pub fn with_meta(mut self, meta: PageMeta) -> Self {
+        self.meta = Some(meta);
+        self
+    }
+
+    pub fn with_error_id(mut self, error_id: String) -> Self {
+        self.error_id = Some(error_id);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    fn row(id: i32, parent_id: Option<i32>, reply_count: i64) -> CommentThreadRow {
+        let at = DateTime::parse_from_rfc3339("2024-11-23T12:00:00+00:00").unwrap();
+        CommentThreadRow {
+            comment: comment::Model {
+                id,
+                post_id: 1,
+                author_id: Some(7),
+                content: format!("comment {}", id),
+                hidden_at: None,
+                created_at: at,
+                updated_at: at,
+                parent_id,
+                depth: 0,
+                deleted_at: None,
+            },
+            reply_count,
+        }
+    }
+
+    fn ids(nodes: &[CommentNodeDto]) -> Vec<i32> {
+        nodes.iter().map(|node| node.comment.id).collect()
+    }
+
+    #[test]
+    fn forest_nests_rows_under_their_parents() {
+        let rows = vec![row(1, None, 2), row(4, None, 0), row(2, Some(1), 1), row(3, Some(1), 0), row(5, Some(2), 0)];
+        let forest = CommentNodeDto::forest(rows, None);
+
+        assert_eq!(ids(&forest), vec![1, 4]);
+        assert_eq!(ids(&forest[0].replies), vec![2, 3]);
+        assert_eq!(ids(&forest[0].replies[0].replies), vec![5]);
+        assert!(forest[1].replies.is_empty());
+        assert_eq!(forest[0].reply_count, 2);
+    }
+
+    #[test]
+    fn forest_starts_below_the_given_root() {
+        let rows = vec![row(2, Some(1), 1), row(3, Some(1), 0), row(5, Some(2), 0)];
+        let forest = CommentNodeDto::forest(rows, Some(1));
+
+        assert_eq!(ids(&forest), vec![2, 3]);
+        assert_eq!(ids(&forest[0].replies), vec![5]);
+    }
+
+    #[test]
+    fn forest_drops_rows_whose_parent_is_missing() {
+        // Replies below a comment that was left out, e.g. a hidden one.
+        let rows = vec![row(1, None, 0), row(8, Some(6), 0), row(9, Some(8), 0)];
+        let forest = CommentNodeDto::forest(rows, None);
+
+        assert_eq!(ids(&forest), vec![1]);
+        assert!(forest[0].replies.is_empty());
+    }
+
+    #[test]
+    fn forest_keeps_placeholders_but_hides_their_content() {
+        let mut placeholder = row(1, None, 1);
+        placeholder.comment.deleted_at = Some(placeholder.comment.created_at);
+        placeholder.comment.content = String::new();
+        let forest = CommentNodeDto::forest(vec![placeholder, row(2, Some(1), 0)], None);
+
+        assert_eq!(forest[0].comment.content, DELETED_COMMENT_PLACEHOLDER);
+        assert_eq!(forest[0].comment.author_id, None);
+        assert_eq!(ids(&forest[0].replies), vec![2]);
+        assert_eq!(forest[0].replies[0].comment.author_id, Some(7));
+    }
 }
@@ -2,7 +2,13 @@
 
 pub mod prelude;
 
+pub mod category;
+pub mod comment;
 pub mod post;
+pub mod post_category;
 pub mod profile;
+pub mod recovery_code;
+pub mod refresh_token;
 pub mod sea_orm_active_enums;
 pub mod user;
+pub mod user_token;
@@ -1,5 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.1
 
+use super::sea_orm_active_enums::PostStatus;
 use sea_orm::entity::prelude::*;
 use serde::{Serialize, Deserialize};
 
@@ -11,7 +12,9 @@ pub struct Model {
     pub title: String,
     #[sea_orm(column_type = "Text")]
     pub content: String,
-    pub published: bool,
+    pub post_status: PostStatus,
+    pub published_at: Option<DateTimeWithTimeZone>,
+    pub scheduled_at: Option<DateTimeWithTimeZone>,
     pub author_id: i32,
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
@@ -19,6 +22,10 @@ pub struct Model {
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::comment::Entity")]
+    Comment,
+    #[sea_orm(has_many = "super::post_category::Entity")]
+    PostCategory,
     #[sea_orm(
         belongs_to = "super::user::Entity",
         from = "Column::AuthorId",
@@ -29,10 +36,31 @@ pub enum Relation {
     User,
 }
 
+impl Related<super::comment::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Comment.def()
+    }
+}
+
+impl Related<super::post_category::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PostCategory.def()
+    }
+}
+
 impl Related<super::user::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::User.def()
     }
 }
 
+impl Related<super::category::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::post_category::Relation::Category.def()
+    }
+    fn via() -> Option<RelationDef> {
+        Some(super::post_category::Relation::Post.def().rev())
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
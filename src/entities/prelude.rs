@@ -1,5 +1,11 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.1
 
+pub use super::category::Entity as Category;
+pub use super::comment::Entity as Comment;
 pub use super::post::Entity as Post;
+pub use super::post_category::Entity as PostCategory;
 pub use super::profile::Entity as Profile;
+pub use super::recovery_code::Entity as RecoveryCode;
+pub use super::refresh_token::Entity as RefreshToken;
 pub use super::user::Entity as User;
+pub use super::user_token::Entity as UserToken;
@@ -9,6 +9,7 @@ use chrono::{DateTime, Utc};
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
+    #[sea_orm(unique)]
     pub user_id: i32,
     pub bio: Option<String>,
     pub avatar: Option<String>,
@@ -3,6 +3,17 @@
 use sea_orm::entity::prelude::*;
 use serde::{Serialize, Deserialize};
 
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "post_status")]
+pub enum PostStatus {
+    #[sea_orm(string_value = "ARCHIVED")]
+    Archived,
+    #[sea_orm(string_value = "DRAFT")]
+    Draft,
+    #[sea_orm(string_value = "PUBLISHED")]
+    Published,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
 #[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "user_role")]
 pub enum UserRole {
@@ -13,3 +24,12 @@ pub enum UserRole {
     #[sea_orm(string_value = "USER")]
     User,
 }
+
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "user_token_purpose")]
+pub enum UserTokenPurpose {
+    #[sea_orm(string_value = "EMAIL_VERIFICATION")]
+    EmailVerification,
+    #[sea_orm(string_value = "PASSWORD_RESET")]
+    PasswordReset,
+}
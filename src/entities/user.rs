@@ -17,14 +17,40 @@ pub struct Model {
     pub user_role: UserRole,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[serde(skip)]
+    pub password_hash: Option<String>,
+    pub verified_at: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    pub totp_secret: Option<String>,
+    pub totp_enabled_at: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    pub totp_last_used_step: Option<i64>,
+    #[serde(skip)]
+    pub failed_login_attempts: i32,
+    #[serde(skip_deserializing)]
+    pub locked_until: Option<DateTime<Utc>>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::comment::Entity")]
+    Comment,
     #[sea_orm(has_many = "super::post::Entity")]
     Post,
     #[sea_orm(has_many = "super::profile::Entity")]
     Profile,
+    #[sea_orm(has_many = "super::recovery_code::Entity")]
+    RecoveryCode,
+    #[sea_orm(has_many = "super::refresh_token::Entity")]
+    RefreshToken,
+    #[sea_orm(has_many = "super::user_token::Entity")]
+    UserToken,
+}
+
+impl Related<super::comment::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Comment.def()
+    }
 }
 
 impl Related<super::post::Entity> for Entity {
@@ -39,4 +65,22 @@ impl Related<super::profile::Entity> for Entity {
     }
 }
 
+impl Related<super::recovery_code::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RecoveryCode.def()
+    }
+}
+
+impl Related<super::refresh_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RefreshToken.def()
+    }
+}
+
+impl Related<super::user_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserToken.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
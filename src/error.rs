@@ -0,0 +1,139 @@
+use std::fmt;
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use sea_orm::{DbErr, SqlErr};
+use uuid::Uuid;
+
+use crate::dto::ApiResponse;
+use crate::validation::FieldError;
+
+/// Error type shared by every handler. Responses only ever carry the public
+/// message; the underlying cause is logged next to an error id that is also
+/// returned to the client, so a report can be matched with the log line.
+#[derive(Debug)]
+pub enum AppError {
+    /// Malformed or invalid input.
+    Validation(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    /// Well formed input that refers to something that does not exist, e.g. a bad `author_id`.
+    Unprocessable(String),
+    /// A body that parsed but broke validation rules; listed per field in the response data.
+    InvalidFields(Vec<FieldError>),
+    Database(DbErr),
+    Internal(String),
+}
+
+impl AppError {
+    pub fn validation(message: impl Into<String>) -> Self {
+        AppError::Validation(message.into())
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        AppError::Unauthorized(message.into())
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        AppError::Forbidden(message.into())
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        AppError::NotFound(message.into())
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        AppError::Conflict(message.into())
+    }
+
+    pub fn unprocessable(message: impl Into<String>) -> Self {
+        AppError::Unprocessable(message.into())
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        AppError::Internal(message.into())
+    }
+
+    /// Message safe to show to clients.
+    pub fn public_message(&self) -> String {
+        match self {
+            AppError::Validation(message)
+            | AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::NotFound(message)
+            | AppError::Conflict(message)
+            | AppError::Unprocessable(message) => message.clone(),
+            AppError::InvalidFields(_) => "Validation failed".to_string(),
+            AppError::Database(err) => match classify_db_error(err) {
+                StatusCode::CONFLICT => "A record with the same unique value already exists".to_string(),
+                StatusCode::UNPROCESSABLE_ENTITY => "A referenced record does not exist".to_string(),
+                StatusCode::NOT_FOUND => "Record not found".to_string(),
+                _ => "Internal server error".to_string(),
+            },
+            AppError::Internal(_) => "Internal server error".to_string(),
+        }
+    }
+}
+
+// Constraint violations are the client's fault; anything else from the
+// database is ours.
+fn classify_db_error(err: &DbErr) -> StatusCode {
+    match err {
+        DbErr::RecordNotFound(_) | DbErr::RecordNotUpdated => StatusCode::NOT_FOUND,
+        _ => match err.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => StatusCode::CONFLICT,
+            Some(SqlErr::ForeignKeyConstraintViolation(_)) => StatusCode::UNPROCESSABLE_ENTITY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        },
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Database(err) => write!(f, "database error: {}", err),
+            AppError::Internal(detail) => write!(f, "internal error: {}", detail),
+            _ => f.write_str(&self.public_message()),
+        }
+    }
+}
+
+impl From<DbErr> for AppError {
+    fn from(err: DbErr) -> Self {
+        AppError::Database(err)
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Unprocessable(_) | AppError::InvalidFields(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Database(err) => classify_db_error(err),
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        let error_id = Uuid::new_v4().to_string();
+        if status.is_server_error() {
+            log::error!("[{}] {}", error_id, self);
+        } else {
+            log::info!("[{}] {} {}", error_id, status.as_u16(), self);
+        }
+
+        let response = ApiResponse::<Vec<FieldError>>::error(status.as_u16(), &self.public_message());
+        let response = match self {
+            AppError::InvalidFields(fields) => ApiResponse { data: Some(fields.clone()), ..response },
+            _ => response,
+        };
+
+        HttpResponse::build(status).json(response.with_error_id(error_id))
+    }
+}
@@ -0,0 +1,497 @@
+use chrono::{DateTime, Utc};
+use sea_orm::{ActiveEnum, Iterable, Order};
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+use crate::entities::sea_orm_active_enums::{PostStatus, UserRole};
+use crate::entities::{post, user};
+use crate::error::AppError;
+use crate::pagination::PageRequest;
+
+// Query strings accepted by the list endpoints. Each endpoint has a fixed
+// whitelist of filters and sort keys; anything else is a 400, so a typo in a
+// filter name never silently returns the unfiltered list.
+
+/// Parses `sort=-created_at,title` against the columns in `allowed`. A leading
+/// `-` sorts descending. Each column may appear once.
+pub fn parse_sort<C: Copy>(spec: Option<&str>, allowed: &[(&str, C)]) -> Result<Vec<(C, Order)>, AppError> {
+    let mut sort = Vec::new();
+    let mut seen: Vec<&str> = Vec::new();
+    for key in spec.unwrap_or_default().split(',').map(str::trim).filter(|key| !key.is_empty()) {
+        let (name, order) = match key.strip_prefix('-') {
+            Some(name) => (name, Order::Desc),
+            None => (key, Order::Asc),
+        };
+        let column = allowed
+            .iter()
+            .find(|(allowed_name, _)| *allowed_name == name)
+            .map(|(_, column)| *column)
+            .ok_or_else(|| {
+                let names: Vec<&str> = allowed.iter().map(|(name, _)| *name).collect();
+                AppError::validation(format!("Cannot sort by `{}`; expected one of {}", name, names.join(", ")))
+            })?;
+        if seen.contains(&name) {
+            return Err(AppError::validation(format!("Cannot sort by `{}` more than once", name)));
+        }
+        seen.push(name);
+        sort.push((column, order));
+    }
+    Ok(sort)
+}
+
+/// Parses `include=profile,posts` against the relations in `allowed`.
+pub fn parse_include<T: Copy + PartialEq>(spec: Option<&str>, allowed: &[(&str, T)]) -> Result<Vec<T>, AppError> {
+    let mut includes = Vec::new();
+    for name in spec.unwrap_or_default().split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        let include = allowed
+            .iter()
+            .find(|(allowed_name, _)| *allowed_name == name)
+            .map(|(_, include)| *include)
+            .ok_or_else(|| {
+                let names: Vec<&str> = allowed.iter().map(|(name, _)| *name).collect();
+                AppError::validation(format!("Cannot include `{}`; expected one of {}", name, names.join(", ")))
+            })?;
+        if !includes.contains(&include) {
+            includes.push(include);
+        }
+    }
+    Ok(includes)
+}
+
+/// Parses `fields=title,created_at` against the columns in `allowed`. The id
+/// is always returned, so `id` is accepted but not listed in the result.
+/// `None` means all fields.
+pub fn parse_fields<C: Copy>(spec: Option<&str>, allowed: &[(&str, C)]) -> Result<Option<Vec<C>>, AppError> {
+    let spec = match spec {
+        Some(spec) if !spec.trim().is_empty() => spec,
+        _ => return Ok(None),
+    };
+
+    let mut names: Vec<&str> = Vec::new();
+    for name in spec.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        if name != "id" && !allowed.iter().any(|(allowed_name, _)| *allowed_name == name) {
+            let mut expected = vec!["id"];
+            expected.extend(allowed.iter().map(|(name, _)| *name));
+            return Err(AppError::validation(format!("Unknown field `{}`; expected one of {}", name, expected.join(", "))));
+        }
+        if name != "id" && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    Ok(Some(
+        allowed
+            .iter()
+            .filter(|(allowed_name, _)| names.contains(allowed_name))
+            .map(|(_, column)| *column)
+            .collect(),
+    ))
+}
+
+/// Accepts the database spelling (`ADMIN`) as well as the JSON one (`Admin`).
+fn db_enum<'de, D, E>(deserializer: D) -> Result<Option<E>, D::Error>
+where
+    D: Deserializer<'de>,
+    E: ActiveEnum<Value = String> + Iterable,
+{
+    let value = String::deserialize(deserializer)?;
+    E::iter()
+        .find(|variant| variant.to_value().eq_ignore_ascii_case(&value))
+        .map(Some)
+        .ok_or_else(|| D::Error::custom(format!("unknown {} `{}`", E::name().to_string(), value)))
+}
+
+fn page_request(page: Option<u64>, per_page: Option<u64>, after: Option<&str>, sorted: bool) -> Result<PageRequest, AppError> {
+    // Cursors encode the position in id order, so they cannot follow another order.
+    if after.is_some() && sorted {
+        return Err(AppError::validation("Cursor pagination cannot be combined with sort"));
+    }
+    PageRequest::new(page, per_page, after)
+}
+
+/// What list endpoints return per row: the whole resource with the relations
+/// from `include`, or only the columns from `fields`.
+#[derive(Debug, Clone)]
+pub enum Projection<I, C> {
+    Full(Vec<I>),
+    Fields(Vec<C>),
+}
+
+fn projection<I, C>(include: Vec<I>, fields: Option<Vec<C>>) -> Result<Projection<I, C>, AppError> {
+    match fields {
+        None => Ok(Projection::Full(include)),
+        Some(_) if !include.is_empty() => Err(AppError::validation("fields cannot be combined with include")),
+        Some(fields) => Ok(Projection::Fields(fields)),
+    }
+}
+
+pub type UserProjection = Projection<UserInclude, user::Column>;
+pub type PostProjection = Projection<PostInclude, post::Column>;
+
+/// Relations that can be embedded in user responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserInclude {
+    Profile,
+    Posts,
+}
+
+pub const USER_INCLUDES: &[(&str, UserInclude)] = &[("profile", UserInclude::Profile), ("posts", UserInclude::Posts)];
+
+/// Relations that can be embedded in post responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostInclude {
+    Author,
+}
+
+pub const POST_INCLUDES: &[(&str, PostInclude)] = &[("author", PostInclude::Author)];
+
+/// Query string of the single-resource reads, e.g. `GET /api/users/{id}?include=profile`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IncludeQuery {
+    pub include: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserListQuery {
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+    pub after: Option<String>,
+    #[serde(default, deserialize_with = "db_enum")]
+    pub user_role: Option<UserRole>,
+    pub email_contains: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub sort: Option<String>,
+    pub include: Option<String>,
+    pub fields: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct UserFilter {
+    pub user_role: Option<UserRole>,
+    pub email_contains: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub sort: Vec<(user::Column, Order)>,
+}
+
+// Secrets and lockout counters are not selectable; `two_factor_enabled` is
+// derived from the TOTP confirmation time.
+const USER_FIELDS: &[(&str, user::Column)] = &[
+    ("email", user::Column::Email),
+    ("first_name", user::Column::FirstName),
+    ("last_name", user::Column::LastName),
+    ("user_role", user::Column::UserRole),
+    ("verified_at", user::Column::VerifiedAt),
+    ("two_factor_enabled", user::Column::TotpEnabledAt),
+    ("created_at", user::Column::CreatedAt),
+    ("updated_at", user::Column::UpdatedAt),
+];
+
+const USER_SORT_KEYS: &[(&str, user::Column)] = &[
+    ("id", user::Column::Id),
+    ("email", user::Column::Email),
+    ("first_name", user::Column::FirstName),
+    ("last_name", user::Column::LastName),
+    ("created_at", user::Column::CreatedAt),
+];
+
+impl UserListQuery {
+    pub fn into_parts(self) -> Result<(UserFilter, PageRequest, UserProjection), AppError> {
+        let sort = parse_sort(self.sort.as_deref(), USER_SORT_KEYS)?;
+        let projection = projection(
+            parse_include(self.include.as_deref(), USER_INCLUDES)?,
+            parse_fields(self.fields.as_deref(), USER_FIELDS)?,
+        )?;
+        let page = page_request(self.page, self.per_page, self.after.as_deref(), !sort.is_empty())?;
+        let filter = UserFilter {
+            user_role: self.user_role,
+            email_contains: self.email_contains,
+            created_after: self.created_after,
+            created_before: self.created_before,
+            sort,
+        };
+        Ok((filter, page, projection))
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostListQuery {
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+    pub after: Option<String>,
+    pub author_id: Option<i32>,
+    #[serde(default, deserialize_with = "db_enum")]
+    pub post_status: Option<PostStatus>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub sort: Option<String>,
+    pub include: Option<String>,
+    pub fields: Option<String>,
+}
+
+/// Which posts a reader may see. Drafts and archived posts are only shown to
+/// their author, moderators and admins.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PostVisibility {
+    All,
+    #[default]
+    Published,
+    PublishedOrAuthor(i32),
+}
+
+impl PostVisibility {
+    pub fn allows(self, post: &post::Model) -> bool {
+        match self {
+            PostVisibility::All => true,
+            PostVisibility::Published => post.post_status == PostStatus::Published,
+            PostVisibility::PublishedOrAuthor(user_id) => post.post_status == PostStatus::Published || post.author_id == user_id,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PostFilter {
+    pub visibility: PostVisibility,
+    pub author_id: Option<i32>,
+    pub post_status: Option<PostStatus>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub sort: Vec<(post::Column, Order)>,
+}
+
+const POST_FIELDS: &[(&str, post::Column)] = &[
+    ("title", post::Column::Title),
+    ("content", post::Column::Content),
+    ("post_status", post::Column::PostStatus),
+    ("published_at", post::Column::PublishedAt),
+    ("scheduled_at", post::Column::ScheduledAt),
+    ("author_id", post::Column::AuthorId),
+    ("created_at", post::Column::CreatedAt),
+    ("updated_at", post::Column::UpdatedAt),
+];
+
+const POST_SORT_KEYS: &[(&str, post::Column)] = &[
+    ("id", post::Column::Id),
+    ("title", post::Column::Title),
+    ("published_at", post::Column::PublishedAt),
+    ("created_at", post::Column::CreatedAt),
+    ("updated_at", post::Column::UpdatedAt),
+];
+
+impl PostListQuery {
+    pub fn into_parts(self, visibility: PostVisibility) -> Result<(PostFilter, PageRequest, PostProjection), AppError> {
+        let sort = parse_sort(self.sort.as_deref(), POST_SORT_KEYS)?;
+        let projection = projection(
+            parse_include(self.include.as_deref(), POST_INCLUDES)?,
+            parse_fields(self.fields.as_deref(), POST_FIELDS)?,
+        )?;
+        let page = page_request(self.page, self.per_page, self.after.as_deref(), !sort.is_empty())?;
+        let filter = PostFilter {
+            visibility,
+            author_id: self.author_id,
+            post_status: self.post_status,
+            created_after: self.created_after,
+            created_before: self.created_before,
+            sort,
+        };
+        Ok((filter, page, projection))
+    }
+}
+
+/// `GET /api/posts/search`. Takes the post filters plus `q`; results are
+/// ranked by relevance unless `sort` is given. Numbered pages only.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostSearchQuery {
+    pub q: String,
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+    /// Accepted only to be refused with a clear message; see `into_parts`.
+    pub after: Option<String>,
+    pub author_id: Option<i32>,
+    #[serde(default, deserialize_with = "db_enum")]
+    pub post_status: Option<PostStatus>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub sort: Option<String>,
+}
+
+impl PostSearchQuery {
+    pub fn into_parts(self, visibility: PostVisibility) -> Result<(String, PostFilter, PageRequest), AppError> {
+        let q = self.q.trim().to_string();
+        if q.is_empty() {
+            return Err(AppError::validation("Search query `q` must not be empty"));
+        }
+        // Relevance is not a stable order to resume from, so there is no cursor to follow.
+        if self.after.is_some() {
+            return Err(AppError::unprocessable("Search results are paged with page, not after"));
+        }
+        let sort = parse_sort(self.sort.as_deref(), POST_SORT_KEYS)?;
+        let page = PageRequest::new(self.page, self.per_page, None)?;
+        let filter = PostFilter {
+            visibility,
+            author_id: self.author_id,
+            post_status: self.post_status,
+            created_after: self.created_after,
+            created_before: self.created_before,
+            sort,
+        };
+        Ok((q, filter, page))
+    }
+}
+
+/// `GET /api/posts/{id}/comments`. Hidden comments are left out unless a
+/// moderator asks for them with `include_hidden=true`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommentListQuery {
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+    pub after: Option<String>,
+    #[serde(default)]
+    pub include_hidden: bool,
+}
+
+impl CommentListQuery {
+    pub fn into_parts(self) -> Result<(PageRequest, bool), AppError> {
+        let page = PageRequest::new(self.page, self.per_page, self.after.as_deref())?;
+        Ok((page, self.include_hidden))
+    }
+}
+
+/// `GET /api/posts/{id}/comments/tree` and `GET /api/comments/{id}/replies`.
+/// Numbered pages only: `page` and `per_page` apply to the requested level.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommentThreadQuery {
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+    #[serde(default)]
+    pub include_hidden: bool,
+}
+
+impl CommentThreadQuery {
+    pub fn into_parts(self) -> Result<(PageRequest, bool), AppError> {
+        let page = PageRequest::new(self.page, self.per_page, None)?;
+        Ok((page, self.include_hidden))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Key {
+        Title,
+        CreatedAt,
+    }
+
+    const KEYS: &[(&str, Key)] = &[("title", Key::Title), ("created_at", Key::CreatedAt)];
+
+    fn rejected<T: std::fmt::Debug>(result: Result<T, AppError>) -> String {
+        match result {
+            Err(AppError::Validation(message)) => message,
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sort_reads_direction_prefix() {
+        let sort = parse_sort(Some("-created_at,title"), KEYS).unwrap();
+        assert_eq!(sort, vec![(Key::CreatedAt, Order::Desc), (Key::Title, Order::Asc)]);
+    }
+
+    #[test]
+    fn sort_skips_empty_segments() {
+        assert_eq!(parse_sort(Some(" title , ,"), KEYS).unwrap(), vec![(Key::Title, Order::Asc)]);
+        assert!(parse_sort(Some(""), KEYS).unwrap().is_empty());
+        assert!(parse_sort(None, KEYS).unwrap().is_empty());
+    }
+
+    #[test]
+    fn sort_rejects_unknown_keys() {
+        assert!(rejected(parse_sort(Some("title,password_hash"), KEYS)).contains("`password_hash`"));
+        assert!(rejected(parse_sort(Some("-"), KEYS)).contains("``"));
+        assert!(rejected(parse_sort(Some("--title"), KEYS)).contains("`-title`"));
+    }
+
+    #[test]
+    fn sort_rejects_duplicate_keys() {
+        assert!(rejected(parse_sort(Some("title,-title"), KEYS)).contains("more than once"));
+    }
+
+    #[test]
+    fn include_deduplicates_and_skips_empty_segments() {
+        assert_eq!(parse_include(Some("title,,title, created_at"), KEYS).unwrap(), vec![Key::Title, Key::CreatedAt]);
+        assert!(parse_include(None, KEYS).unwrap().is_empty());
+    }
+
+    #[test]
+    fn include_rejects_unknown_relations() {
+        assert!(rejected(parse_include(Some("title,author"), KEYS)).contains("`author`"));
+        assert!(rejected(parse_include(Some("-title"), KEYS)).contains("`-title`"));
+    }
+
+    #[test]
+    fn fields_keep_table_order_without_duplicates() {
+        let fields = parse_fields(Some("created_at,title,created_at"), KEYS).unwrap();
+        assert_eq!(fields, Some(vec![Key::Title, Key::CreatedAt]));
+    }
+
+    #[test]
+    fn fields_accept_id_but_do_not_list_it() {
+        assert_eq!(parse_fields(Some("id"), KEYS).unwrap(), Some(vec![]));
+        assert_eq!(parse_fields(Some("id,title,"), KEYS).unwrap(), Some(vec![Key::Title]));
+    }
+
+    #[test]
+    fn blank_fields_mean_all_fields() {
+        assert_eq!(parse_fields(None, KEYS).unwrap(), None);
+        assert_eq!(parse_fields(Some(" "), KEYS).unwrap(), None);
+    }
+
+    #[test]
+    fn fields_reject_unknown_names() {
+        assert!(rejected(parse_fields(Some("title,secret"), KEYS)).contains("`secret`"));
+    }
+
+    fn post(author_id: i32, post_status: PostStatus) -> post::Model {
+        let now = Utc::now().fixed_offset();
+        post::Model {
+            id: 1,
+            title: "Title".to_string(),
+            content: "Content".to_string(),
+            post_status,
+            published_at: None,
+            scheduled_at: None,
+            author_id,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn visibility_hides_unpublished_posts_from_other_readers() {
+        let draft = post(7, PostStatus::Draft);
+        let archived = post(7, PostStatus::Archived);
+        let published = post(7, PostStatus::Published);
+
+        assert!(PostVisibility::Published.allows(&published));
+        assert!(!PostVisibility::Published.allows(&draft));
+        assert!(!PostVisibility::Published.allows(&archived));
+        assert!(!PostVisibility::PublishedOrAuthor(8).allows(&draft));
+        assert!(PostVisibility::PublishedOrAuthor(7).allows(&draft));
+        assert!(PostVisibility::PublishedOrAuthor(7).allows(&archived));
+        assert!(PostVisibility::All.allows(&archived));
+    }
+
+    #[test]
+    fn search_rejects_a_cursor() {
+        let query = PostSearchQuery { q: "rust".to_string(), after: Some("abc".to_string()), ..Default::default() };
+        assert!(matches!(query.into_parts(PostVisibility::All), Err(AppError::Unprocessable(_))));
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::sync::OnceLock;
+
+use actix_web::{HttpResponse, Responder};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use serde::Serialize;
+use simple_asn1::{from_der, ASN1Block};
+
+/// Signing and verification keys, loaded once from the environment.
+///
+/// `JWT_ALGORITHM` selects `HS256` (default), `RS256` or `EdDSA`.
+/// - `HS256`: `JWT_SECRET`, plus `JWT_PREVIOUS_SECRETS=kid:secret,...` during a rotation.
+/// - `RS256`/`EdDSA`: `JWT_PRIVATE_KEY_FILE` and `JWT_PUBLIC_KEY_FILE` (PEM), plus
+///   `JWT_PREVIOUS_PUBLIC_KEYS=kid:path,...` for keys that must still verify.
+///
+/// `JWT_KEY_ID` is the `kid` written into every new token.
+pub struct JwtKeys {
+    pub algorithm: Algorithm,
+    pub signing_kid: String,
+    encoding_key: EncodingKey,
+    verification_keys: HashMap<String, VerificationKey>,
+}
+
+struct VerificationKey {
+    decoding_key: DecodingKey,
+    jwk: Option<Jwk>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Jwk {
+    pub kty: &'static str,
+    #[serde(rename = "use")]
+    pub use_: &'static str,
+    pub alg: &'static str,
+    pub kid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crv: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub e: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+pub fn keys() -> &'static JwtKeys {
+    static KEYS: OnceLock<JwtKeys> = OnceLock::new();
+    KEYS.get_or_init(|| {
+        JwtKeys::from_env().unwrap_or_else(|err| panic!("Invalid JWT configuration: {}", err))
+    })
+}
+
+impl JwtKeys {
+    pub fn from_env() -> Result<Self, String> {
+        let algorithm = match env::var("JWT_ALGORITHM").as_deref() {
+            Ok("HS256") | Err(_) => Algorithm::HS256,
+            Ok("RS256") => Algorithm::RS256,
+            Ok("EdDSA") => Algorithm::EdDSA,
+            Ok(other) => return Err(format!("unsupported JWT_ALGORITHM {}", other)),
+        };
+        let signing_kid = env::var("JWT_KEY_ID").unwrap_or_else(|_| "primary".to_string());
+        let mut verification_keys = HashMap::new();
+
+        let encoding_key = if algorithm == Algorithm::HS256 {
+            let secret = env::var("JWT_SECRET").map_err(|_| "JWT_SECRET must be set".to_string())?;
+            verification_keys.insert(
+                signing_kid.clone(),
+                VerificationKey { decoding_key: DecodingKey::from_secret(secret.as_bytes()), jwk: None },
+            );
+            for (kid, secret) in parse_key_list("JWT_PREVIOUS_SECRETS") {
+                verification_keys.insert(
+                    kid,
+                    VerificationKey { decoding_key: DecodingKey::from_secret(secret.as_bytes()), jwk: None },
+                );
+            }
+            EncodingKey::from_secret(secret.as_bytes())
+        } else {
+            let private_pem = read_key_file("JWT_PRIVATE_KEY_FILE")?;
+            let public_pem = read_key_file("JWT_PUBLIC_KEY_FILE")?;
+            verification_keys.insert(
+                signing_kid.clone(),
+                load_public_key(algorithm, &signing_kid, &public_pem)?,
+            );
+            for (kid, path) in parse_key_list("JWT_PREVIOUS_PUBLIC_KEYS") {
+                let pem = fs::read(&path).map_err(|err| format!("cannot read {}: {}", path, err))?;
+                let key = load_public_key(algorithm, &kid, &pem)?;
+                verification_keys.insert(kid, key);
+            }
+            match algorithm {
+                Algorithm::RS256 => EncodingKey::from_rsa_pem(&private_pem),
+                _ => EncodingKey::from_ed_pem(&private_pem),
+            }
+            .map_err(|err| format!("invalid JWT_PRIVATE_KEY_FILE: {}", err))?
+        };
+
+        Ok(Self { algorithm, signing_kid, encoding_key, verification_keys })
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    /// Picks the verification key named by the token's `kid`; tokens without
+    /// a `kid` are checked against the current signing key.
+    pub fn decoding_key(&self, kid: Option<&str>) -> Option<&DecodingKey> {
+        let kid = kid.unwrap_or(&self.signing_kid);
+        self.verification_keys.get(kid).map(|key| &key.decoding_key)
+    }
+
+    /// Public keys only; shared secrets are never published.
+    pub fn jwk_set(&self) -> JwkSet {
+        let mut keys: Vec<Jwk> = self
+            .verification_keys
+            .values()
+            .filter_map(|key| key.jwk.clone())
+            .collect();
+        keys.sort_by(|a, b| a.kid.cmp(&b.kid));
+        JwkSet { keys }
+    }
+}
+
+fn read_key_file(var: &str) -> Result<Vec<u8>, String> {
+    let path = env::var(var).map_err(|_| format!("{} must be set", var))?;
+    fs::read(&path).map_err(|err| format!("cannot read {}: {}", path, err))
+}
+
+// Parses `kid:value,kid:value` lists.
+fn parse_key_list(var: &str) -> Vec<(String, String)> {
+    env::var(var)
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|entry| {
+            let (kid, value) = entry.trim().split_once(':')?;
+            Some((kid.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+fn load_public_key(algorithm: Algorithm, kid: &str, pem_bytes: &[u8]) -> Result<VerificationKey, String> {
+    let decoding_key = match algorithm {
+        Algorithm::RS256 => DecodingKey::from_rsa_pem(pem_bytes),
+        _ => DecodingKey::from_ed_pem(pem_bytes),
+    }
+    .map_err(|err| format!("invalid public key {}: {}", kid, err))?;
+
+    let jwk = public_key_jwk(algorithm, kid, pem_bytes)
+        .ok_or_else(|| format!("public key {} is not a PEM encoded SubjectPublicKeyInfo", kid))?;
+
+    Ok(VerificationKey { decoding_key, jwk: Some(jwk) })
+}
+
+// Extracts the raw key material from a `BEGIN PUBLIC KEY` (SubjectPublicKeyInfo) PEM.
+fn public_key_jwk(algorithm: Algorithm, kid: &str, pem_bytes: &[u8]) -> Option<Jwk> {
+    let pem = pem::parse(pem_bytes).ok()?;
+    let blocks = from_der(pem.contents()).ok()?;
+    let key_bits = match blocks.first()? {
+        ASN1Block::Sequence(_, items) => match items.get(1)? {
+            ASN1Block::BitString(_, _, bits) => bits.clone(),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    if algorithm == Algorithm::RS256 {
+        let (n, e) = match from_der(&key_bits).ok()?.first()? {
+            ASN1Block::Sequence(_, items) => match (items.first()?, items.get(1)?) {
+                (ASN1Block::Integer(_, n), ASN1Block::Integer(_, e)) => (n.to_bytes_be().1, e.to_bytes_be().1),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(Jwk {
+            kty: "RSA",
+            use_: "sig",
+            alg: "RS256",
+            kid: kid.to_string(),
+            crv: None,
+            n: Some(URL_SAFE_NO_PAD.encode(n)),
+            e: Some(URL_SAFE_NO_PAD.encode(e)),
+            x: None,
+        })
+    } else {
+        Some(Jwk {
+            kty: "OKP",
+            use_: "sig",
+            alg: "EdDSA",
+            kid: kid.to_string(),
+            crv: Some("Ed25519"),
+            n: None,
+            e: None,
+            x: Some(URL_SAFE_NO_PAD.encode(key_bits)),
+        })
+    }
+}
+
+pub async fn jwks() -> impl Responder {
+    HttpResponse::Ok().json(keys().jwk_set())
+}
@@ -1,10 +1,23 @@
 pub mod models;
+pub mod account;
+pub mod auth;
+pub mod jwt;
+pub mod login_throttle;
+pub mod mailer;
 pub mod api;
 pub mod dto;
 pub mod entities;
+pub mod error;
+pub mod filters;
 pub mod repository;
+pub mod scheduler;
 pub mod server;
+pub mod two_factor;
 pub mod database;
+pub mod pagination;
+pub mod password;
+pub mod permissions;
+pub mod validation;
 
 pub use models::*;
 pub use api::*;
@@ -0,0 +1,154 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::env;
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+/// How failed logins are punished. Read from `LOGIN_MAX_FAILURES`,
+/// `LOGIN_LOCKOUT_MINUTES`, `LOGIN_BACKOFF_BASE_SECONDS` and `LOGIN_IP_MAX_FAILURES`.
+#[derive(Debug, Clone)]
+pub struct LockoutPolicy {
+    /// Failures per account before it is locked for `lockout`.
+    pub max_failures: i32,
+    /// Failures per client address before it is locked for `lockout`. Higher
+    /// than `max_failures` because several users can share an address.
+    pub ip_max_failures: i32,
+    pub lockout: Duration,
+    pub backoff_base: Duration,
+}
+
+impl LockoutPolicy {
+    pub fn from_env() -> Self {
+        fn number(name: &str, default: i64) -> i64 {
+            env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        }
+
+        Self {
+            max_failures: number("LOGIN_MAX_FAILURES", 5) as i32,
+            ip_max_failures: number("LOGIN_IP_MAX_FAILURES", 20) as i32,
+            lockout: Duration::minutes(number("LOGIN_LOCKOUT_MINUTES", 15)),
+            backoff_base: Duration::seconds(number("LOGIN_BACKOFF_BASE_SECONDS", 1)),
+        }
+    }
+
+    /// How long to refuse further attempts after `failures` consecutive failures:
+    /// the delay doubles with every failure and turns into the full lockout at `limit`.
+    /// Until then it is capped at `lockout / limit`, so a shared address is not
+    /// effectively locked out long before it reaches its own limit.
+    pub fn delay_after(&self, failures: i32, limit: i32) -> Duration {
+        if failures <= 0 {
+            return Duration::zero();
+        }
+        if failures >= limit {
+            return self.lockout;
+        }
+
+        let delay = self.backoff_base * 2i32.saturating_pow((failures - 1) as u32);
+        delay.min(self.lockout / limit)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct AddressState {
+    failures: i32,
+    blocked_until: DateTime<Utc>,
+}
+
+/// Per client address failure tracking. Kept in memory: it only slows down a
+/// single attacker hammering one instance, the per-account lock in the
+/// `user` table is what holds across instances.
+pub struct LoginThrottle {
+    pub policy: LockoutPolicy,
+    addresses: Mutex<HashMap<IpAddr, AddressState>>,
+}
+
+impl LoginThrottle {
+    pub fn new(policy: LockoutPolicy) -> Self {
+        Self { policy, addresses: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn is_blocked(&self, ip: IpAddr) -> bool {
+        let addresses = self.addresses.lock().expect("login throttle lock poisoned");
+        addresses
+            .get(&ip)
+            .is_some_and(|state| state.blocked_until > Utc::now())
+    }
+
+    pub fn record_failure(&self, ip: IpAddr) {
+        let now = Utc::now();
+        let mut addresses = self.addresses.lock().expect("login throttle lock poisoned");
+
+        // Forget addresses whose lock ran out long ago so the map cannot grow forever.
+        let forget_before = now - self.policy.lockout;
+        addresses.retain(|_, state| state.blocked_until > forget_before);
+
+        let state = addresses.entry(ip).or_insert(AddressState { failures: 0, blocked_until: now });
+        state.failures += 1;
+        state.blocked_until = now + self.policy.delay_after(state.failures, self.policy.ip_max_failures);
+    }
+
+    pub fn record_success(&self, ip: IpAddr) {
+        self.addresses
+            .lock()
+            .expect("login throttle lock poisoned")
+            .remove(&ip);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> LockoutPolicy {
+        LockoutPolicy {
+            max_failures: 5,
+            ip_max_failures: 20,
+            lockout: Duration::minutes(15),
+            backoff_base: Duration::seconds(1),
+        }
+    }
+
+    #[test]
+    fn no_delay_without_failures() {
+        assert_eq!(policy().delay_after(0, 5), Duration::zero());
+    }
+
+    #[test]
+    fn first_failure_waits_the_base_delay() {
+        assert_eq!(policy().delay_after(1, 5), Duration::seconds(1));
+    }
+
+    #[test]
+    fn delay_doubles_with_every_failure() {
+        let policy = policy();
+        assert_eq!(policy.delay_after(2, 5), Duration::seconds(2));
+        assert_eq!(policy.delay_after(3, 5), Duration::seconds(4));
+        assert_eq!(policy.delay_after(4, 5), Duration::seconds(8));
+    }
+
+    #[test]
+    fn backoff_is_capped_below_the_lockout() {
+        let policy = policy();
+        assert_eq!(policy.delay_after(11, 100), Duration::seconds(9));
+        assert_eq!(policy.delay_after(99, 100), Duration::seconds(9));
+    }
+
+    #[test]
+    fn full_lockout_only_starts_at_the_limit() {
+        let policy = policy();
+        for limit in [policy.max_failures, policy.ip_max_failures] {
+            assert!(policy.delay_after(limit - 1, limit) < policy.lockout);
+            assert_eq!(policy.delay_after(limit, limit), policy.lockout);
+        }
+    }
+
+    #[test]
+    fn max_failures_locks_for_the_full_lockout() {
+        let policy = policy();
+        assert_eq!(policy.delay_after(policy.max_failures, policy.max_failures), Duration::minutes(15));
+        assert_eq!(policy.delay_after(policy.max_failures + 3, policy.max_failures), Duration::minutes(15));
+    }
+}
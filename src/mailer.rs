@@ -0,0 +1,71 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub struct EmailMessage {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Outgoing email transport. Handlers only see `web::Data<dyn Mailer>`, so a
+/// real SMTP or API backed implementation can be dropped in without touching them.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, message: EmailMessage) -> Result<(), String>;
+}
+
+/// Prints every message to stdout. The default for local development.
+pub struct StdoutMailer;
+
+#[async_trait]
+impl Mailer for StdoutMailer {
+    async fn send(&self, message: EmailMessage) -> Result<(), String> {
+        println!(
+            "To: {}\nSubject: {}\n\n{}\n",
+            message.to, message.subject, message.body
+        );
+        Ok(())
+    }
+}
+
+/// Writes each message to its own file in `dir`, so tests can pick the tokens up.
+pub struct FileMailer {
+    pub dir: PathBuf,
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, message: EmailMessage) -> Result<(), String> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|err| format!("Failed to create mail directory: {}", err))?;
+
+        let file_name = format!(
+            "{}-{}.eml",
+            Utc::now().format("%Y%m%dT%H%M%S%.6f"),
+            message.to.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        );
+        let contents = format!(
+            "To: {}\nSubject: {}\n\n{}\n",
+            message.to, message.subject, message.body
+        );
+
+        tokio::fs::write(self.dir.join(file_name), contents)
+            .await
+            .map_err(|err| format!("Failed to write email: {}", err))
+    }
+}
+
+/// Picks the mailer from `MAILER` (`stdout` or `file`); `file` writes to `MAILER_DIR`.
+pub fn mailer_from_env() -> Arc<dyn Mailer> {
+    match env::var("MAILER").as_deref() {
+        Ok("file") => Arc::new(FileMailer {
+            dir: env::var("MAILER_DIR").unwrap_or_else(|_| "mail".to_string()).into(),
+        }),
+        _ => Arc::new(StdoutMailer),
+    }
+}
@@ -6,6 +6,7 @@ use dotenv::dotenv;
 async fn main() -> std::io::Result<()> {
     // Load environment variables
     dotenv().ok();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
     // Create database connection pool
     let db = create_pool()
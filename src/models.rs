@@ -8,13 +8,6 @@ pub enum UserRole {
     MODERATOR,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum PostStatus {
-    DRAFT,
-    PUBLISHED,
-    ARCHIVED,
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub id: Option<i32>,
@@ -35,29 +28,3 @@ pub struct Profile {
     pub phone_number: Option<String>,
     pub birth_date: Option<DateTime<Utc>>,
 }
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Post {
-    pub id: Option<i32>,
-    pub title: String,
-    pub content: String,
-    pub post_status: PostStatus,
-    pub view_count: i32,
-    pub author_id: i32,
-    pub published_at: Option<DateTime<Utc>>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Category {
-    pub id: Option<i32>,
-    pub category_name: String,
-    pub description: Option<String>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Comment {
-    pub id: Option<i32>,
-    pub content: String,
-    pub post_id: i32,
-    pub created_at: Option<DateTime<Utc>>,
-}
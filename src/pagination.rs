@@ -0,0 +1,243 @@
+use actix_web::HttpRequest;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+
+pub const DEFAULT_PER_PAGE: u64 = 20;
+pub const MAX_PER_PAGE: u64 = 100;
+
+/// `?page=&per_page=` for numbered pages, or `?after=&per_page=` to continue
+/// from an opaque cursor. `per_page` is capped at `MAX_PER_PAGE`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PageQuery {
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PageRequest {
+    Offset { page: u64, per_page: u64 },
+    /// Rows with an id greater than `after`.
+    Cursor { after: i32, per_page: u64 },
+}
+
+impl PageRequest {
+    pub fn new(page: Option<u64>, per_page: Option<u64>, after: Option<&str>) -> Result<Self, AppError> {
+        let per_page = per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+
+        match (after, page) {
+            (Some(_), Some(_)) => Err(AppError::validation("Use either page or after, not both")),
+            (Some(cursor), None) => Ok(PageRequest::Cursor { after: decode_cursor(cursor)?, per_page }),
+            (None, page) => {
+                let page = page.unwrap_or(1).max(1);
+                // The end of the page has to fit the database's signed 64-bit OFFSET.
+                if page.checked_mul(per_page).is_none_or(|end| end > i64::MAX as u64) {
+                    return Err(AppError::validation("page is out of range"));
+                }
+                Ok(PageRequest::Offset { page, per_page })
+            }
+        }
+    }
+
+    /// Rows to skip for a numbered page; cursor pages start at the cursor instead.
+    pub fn offset(&self) -> u64 {
+        match self {
+            PageRequest::Offset { page, per_page } => (page - 1) * per_page,
+            PageRequest::Cursor { .. } => 0,
+        }
+    }
+
+    pub fn per_page(&self) -> u64 {
+        match self {
+            PageRequest::Offset { per_page, .. } | PageRequest::Cursor { per_page, .. } => *per_page,
+        }
+    }
+}
+
+impl TryFrom<&PageQuery> for PageRequest {
+    type Error = AppError;
+
+    fn try_from(query: &PageQuery) -> Result<Self, AppError> {
+        PageRequest::new(query.page, query.per_page, query.after.as_deref())
+    }
+}
+
+/// One page of rows as returned by the repository.
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: u64,
+    /// Id of the last row, set when more rows follow.
+    pub next_after: Option<i32>,
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+            next_after: self.next_after,
+        }
+    }
+}
+
+/// Cursors only wrap the id today, but clients must treat them as opaque so the
+/// encoding can change when other sort orders get cursor support.
+pub fn encode_cursor(id: i32) -> String {
+    URL_SAFE_NO_PAD.encode(format!("id:{}", id))
+}
+
+pub fn decode_cursor(cursor: &str) -> Result<i32, AppError> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .and_then(|value| value.strip_prefix("id:").and_then(|id| id.parse().ok()))
+        .ok_or_else(|| AppError::validation("Invalid cursor"))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PageMeta {
+    pub total: u64,
+    pub per_page: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_pages: Option<u64>,
+    pub next_cursor: Option<String>,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+impl PageMeta {
+    /// Builds the metadata and the next/prev links. Links keep every other query
+    /// parameter of the current request, so filters carry over between pages.
+    pub fn new<T>(req: &HttpRequest, request: &PageRequest, page: &Page<T>) -> Self {
+        let per_page = request.per_page();
+        let next_cursor = page.next_after.map(encode_cursor);
+
+        match *request {
+            PageRequest::Offset { page: number, .. } => {
+                let total_pages = page.total.div_ceil(per_page).max(1);
+                let link = |number: u64| {
+                    page_link(req, &[("page", number.to_string()), ("per_page", per_page.to_string())])
+                };
+                let next = (number < total_pages).then(|| link(number + 1));
+                // Past the end, point back at the last page rather than at another empty one.
+                let prev = (number > 1).then(|| link((number - 1).min(total_pages)));
+
+                PageMeta {
+                    total: page.total,
+                    per_page,
+                    page: Some(number),
+                    total_pages: Some(total_pages),
+                    next_cursor,
+                    next,
+                    prev,
+                }
+            }
+            PageRequest::Cursor { .. } => PageMeta {
+                total: page.total,
+                per_page,
+                page: None,
+                total_pages: None,
+                next: next_cursor
+                    .as_ref()
+                    .map(|cursor| page_link(req, &[("after", cursor.clone()), ("per_page", per_page.to_string())])),
+                next_cursor,
+                prev: None,
+            },
+        }
+    }
+}
+
+fn page_link(req: &HttpRequest, params: &[(&str, String)]) -> String {
+    let mut query: Vec<(String, String)> = serde_urlencoded::from_str(req.query_string()).unwrap_or_default();
+    query.retain(|(key, _)| !matches!(key.as_str(), "page" | "per_page" | "after"));
+    query.extend(params.iter().map(|(key, value)| (key.to_string(), value.clone())));
+
+    match serde_urlencoded::to_string(&query) {
+        Ok(query) if !query.is_empty() => format!("{}?{}", req.path(), query),
+        _ => req.path().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn page(total: u64, next_after: Option<i32>) -> Page<()> {
+        Page { items: Vec::new(), total, next_after }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        assert_eq!(decode_cursor(&encode_cursor(40)).unwrap(), 40);
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        for cursor in ["", "not base64!", "aWQ6", "aWQ6YWJj", "eDo0MA", "aWQ6OTk5OTk5OTk5OTk5"] {
+            assert!(decode_cursor(cursor).is_err(), "accepted {:?}", cursor);
+        }
+    }
+
+    #[test]
+    fn tampered_cursors_are_rejected() {
+        let mut cursor = encode_cursor(40);
+        cursor.push('A');
+        assert!(decode_cursor(&cursor).is_err());
+        assert!(decode_cursor(&URL_SAFE_NO_PAD.encode("id:40;drop")).is_err());
+        assert!(decode_cursor(&URL_SAFE_NO_PAD.encode("id:-")).is_err());
+    }
+
+    #[test]
+    fn page_and_cursor_cannot_be_combined() {
+        assert!(PageRequest::new(Some(2), None, Some(&encode_cursor(1))).is_err());
+    }
+
+    #[test]
+    fn pages_past_the_offset_range_are_rejected() {
+        assert!(PageRequest::new(Some(u64::MAX), Some(100), None).is_err());
+        assert!(PageRequest::new(Some(i64::MAX as u64), Some(2), None).is_err());
+        let last = PageRequest::new(Some(i64::MAX as u64 / 100), Some(100), None).unwrap();
+        assert_eq!(last.offset(), (i64::MAX as u64 / 100 - 1) * 100);
+    }
+
+    #[test]
+    fn full_last_page_has_no_next_link() {
+        let req = TestRequest::with_uri("/api/posts?page=2&per_page=20").to_http_request();
+        let request = PageRequest::new(Some(2), Some(20), None).unwrap();
+        let meta = PageMeta::new(&req, &request, &page(40, None));
+        assert_eq!(meta.total_pages, Some(2));
+        assert_eq!(meta.next, None);
+        assert_eq!(meta.prev.as_deref(), Some("/api/posts?page=1&per_page=20"));
+    }
+
+    #[test]
+    fn one_more_row_adds_a_next_link() {
+        let req = TestRequest::with_uri("/api/posts?author_id=5&page=2&per_page=20").to_http_request();
+        let request = PageRequest::new(Some(2), Some(20), None).unwrap();
+        let meta = PageMeta::new(&req, &request, &page(41, Some(40)));
+        assert_eq!(meta.total_pages, Some(3));
+        assert_eq!(meta.next.as_deref(), Some("/api/posts?author_id=5&page=3&per_page=20"));
+        assert_eq!(meta.next_cursor, Some(encode_cursor(40)));
+    }
+
+    #[test]
+    fn cursor_pages_link_to_the_next_cursor_only() {
+        let req = TestRequest::with_uri("/api/posts").to_http_request();
+        let request = PageRequest::new(None, Some(20), Some(&encode_cursor(20))).unwrap();
+        let meta = PageMeta::new(&req, &request, &page(41, Some(40)));
+        assert_eq!(meta.next, Some(format!("/api/posts?after={}&per_page=20", encode_cursor(40))));
+        assert_eq!(meta.prev, None);
+
+        let last = PageMeta::new(&req, &request, &page(41, None));
+        assert_eq!(last.next, None);
+        assert_eq!(last.next_cursor, None);
+    }
+}
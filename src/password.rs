@@ -0,0 +1,56 @@
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use std::sync::OnceLock;
+
+const MIN_PASSWORD_LENGTH: usize = 10;
+const MAX_PASSWORD_LENGTH: usize = 128;
+
+// Argon2::default() is Argon2id (v19) with the OWASP-recommended parameters.
+fn argon2() -> Argon2<'static> {
+    Argon2::default()
+}
+
+pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(argon2().hash_password(password.as_bytes(), &salt)?.to_string())
+}
+
+// The underlying comparison is constant-time; a malformed stored hash is treated as a mismatch.
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(parsed) => argon2().verify_password(password.as_bytes(), &parsed).is_ok(),
+        Err(_) => false,
+    }
+}
+
+// Burns the same amount of work as a real verification so that unknown emails
+// and accounts without a password cannot be told apart by response time.
+pub fn dummy_verify(password: &str) {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    let hash = DUMMY_HASH.get_or_init(|| {
+        hash_password("dummy-password").expect("hashing a constant password succeeds")
+    });
+    verify_password(password, hash);
+}
+
+/// Strength rules for passwords chosen by users themselves.
+pub fn check_password_strength(password: &str, email: &str) -> Result<(), String> {
+    let length = password.chars().count();
+    if length < MIN_PASSWORD_LENGTH {
+        return Err(format!("Password must be at least {} characters long", MIN_PASSWORD_LENGTH));
+    }
+    if length > MAX_PASSWORD_LENGTH {
+        return Err(format!("Password must be at most {} characters long", MAX_PASSWORD_LENGTH));
+    }
+    if !password.chars().any(|c| c.is_lowercase())
+        || !password.chars().any(|c| c.is_uppercase())
+        || !password.chars().any(|c| c.is_ascii_digit())
+    {
+        return Err("Password must contain lowercase and uppercase letters and a digit".to_string());
+    }
+    if password.eq_ignore_ascii_case(email) {
+        return Err("Password must not be the same as the email address".to_string());
+    }
+
+    Ok(())
+}
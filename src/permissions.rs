@@ -0,0 +1,115 @@
+use std::marker::PhantomData;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{Error as ActixError, FromRequest};
+use futures::future::{ready, Ready};
+
+use crate::auth::AuthMiddleware;
+use crate::entities::sea_orm_active_enums::UserRole;
+use crate::error::AppError;
+
+// Policies used by the handlers. Keeping them here makes the access rules
+// reviewable in one place instead of scattered through api.rs.
+
+/// Only admins may assign or change `user_role`.
+pub const CHANGE_USER_ROLE: &[UserRole] = &[UserRole::Admin];
+/// Roles that may archive or unpublish any post, regardless of who wrote it.
+pub const MODERATE_POSTS: &[UserRole] = &[UserRole::Admin, UserRole::Moderator];
+/// Roles that may hide and unhide comments, and list hidden ones.
+pub const MODERATE_COMMENTS: &[UserRole] = &[UserRole::Admin, UserRole::Moderator];
+/// Roles that may create, rename and delete categories, and file any post
+/// under them. Authors can categorise their own posts.
+pub const MANAGE_CATEGORIES: &[UserRole] = &[UserRole::Admin, UserRole::Moderator];
+/// Roles that may manage users, posts and profiles owned by somebody else.
+pub const MANAGE_OTHERS: &[UserRole] = &[UserRole::Admin];
+
+/// A fixed set of roles accepted by `RequireRole` and `require_role`.
+pub trait RoleSet {
+    const ROLES: &'static [UserRole];
+}
+
+pub struct AdminOnly;
+
+impl RoleSet for AdminOnly {
+    const ROLES: &'static [UserRole] = &[UserRole::Admin];
+}
+
+pub struct AdminOrModerator;
+
+impl RoleSet for AdminOrModerator {
+    const ROLES: &'static [UserRole] = &[UserRole::Admin, UserRole::Moderator];
+}
+
+pub fn parse_role(role: &str) -> Option<UserRole> {
+    match role {
+        "Admin" => Some(UserRole::Admin),
+        "Moderator" => Some(UserRole::Moderator),
+        "User" => Some(UserRole::User),
+        _ => None,
+    }
+}
+
+impl AuthMiddleware {
+    pub fn user_role(&self) -> Option<UserRole> {
+        parse_role(&self.role)
+    }
+
+    pub fn has_any_role(&self, roles: &[UserRole]) -> bool {
+        self.user_role().is_some_and(|role| roles.contains(&role))
+    }
+
+    /// Passes when the caller holds one of `roles`.
+    pub fn require_any_role(&self, roles: &[UserRole]) -> Result<(), AppError> {
+        if self.has_any_role(roles) {
+            Ok(())
+        } else {
+            Err(AppError::forbidden("Insufficient permissions"))
+        }
+    }
+
+    /// Passes when the caller holds one of `roles` or is the owner of the resource.
+    pub fn require_role_or_owner(&self, roles: &[UserRole], owner_id: i32) -> Result<(), AppError> {
+        if self.user_id == owner_id || self.has_any_role(roles) {
+            Ok(())
+        } else {
+            Err(AppError::forbidden("Insufficient permissions"))
+        }
+    }
+}
+
+/// Extractor for routes restricted to the roles in `R`, e.g. `RequireRole<AdminOnly>`.
+pub struct RequireRole<R: RoleSet> {
+    pub auth: AuthMiddleware,
+    _roles: PhantomData<R>,
+}
+
+impl<R: RoleSet> FromRequest for RequireRole<R> {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let result = AuthMiddleware::from_request(req, payload)
+            .into_inner()
+            .and_then(|auth| {
+                auth.require_any_role(R::ROLES)?;
+                Ok(RequireRole { auth, _roles: PhantomData })
+            });
+
+        ready(result)
+    }
+}
+
+/// Scope-level variant of `RequireRole`, used with `middleware::from_fn(require_role::<AdminOnly>)`.
+pub async fn require_role<R: RoleSet>(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, ActixError> {
+    let auth = req.extract::<AuthMiddleware>().await?;
+    auth.require_any_role(R::ROLES)?;
+    next.call(req).await
+}
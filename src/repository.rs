@@ -1,12 +1,112 @@
 use sea_orm::*;
-use crate::entities::{user, profile, post};
+use crate::entities::{user, profile, post, category, comment, post_category, recovery_code, refresh_token, user_token};
+use crate::entities::sea_orm_active_enums::{PostStatus, UserTokenPurpose};
 use chrono::{DateTime, FixedOffset, Utc};
-use crate::dto::UserCreateDto;
+use crate::dto::{
+    CategoryCreateDto, CategoryPatchDto, CategoryUpdateDto, CommentCreateDto, CommentUpdateDto, PostCreateDto,
+    PostFieldsDto, PostPatchDto, PostUpdateDto, ProfileCreateDto, ProfilePatchDto, ProfileUpdateDto, UserCreateDto,
+    UserFieldsDto, UserPatchDto, UserUpdateDto,
+};
+use crate::filters::{PostFilter, PostVisibility, UserFilter};
+use crate::pagination::{Page, PageRequest};
+use crate::password::hash_password;
+use sea_orm::sea_query::{extension::postgres::PgExpr, Expr, LikeExpr, LockBehavior, LockType};
+use uuid::Uuid;
+use std::collections::HashMap;
 
 pub struct Repository {
     db: DatabaseConnection,
 }
 
+/// The posts a list covers.
+#[derive(Debug, Clone, Copy)]
+pub enum PostScope<'a> {
+    All,
+    ByAuthor(&'a user::Model),
+    InCategory(&'a category::Model),
+}
+
+impl PostScope<'_> {
+    fn select(self) -> Select<post::Entity> {
+        match self {
+            PostScope::All => post::Entity::find(),
+            PostScope::ByAuthor(user) => user.find_related(post::Entity),
+            PostScope::InCategory(category) => category.find_related(post::Entity),
+        }
+    }
+}
+
+/// Replies can nest this many levels below a top-level comment.
+pub const MAX_COMMENT_DEPTH: i32 = 5;
+/// Levels of replies returned below the requested level of a thread.
+pub const THREAD_LEVELS: i32 = 3;
+/// Replies returned per comment below the requested level; the rest are
+/// fetched from `/api/comments/{id}/replies`.
+pub const REPLIES_PER_LEVEL: i64 = 5;
+
+/// A comment returned by `find_comment_thread`, with its number of direct replies.
+#[derive(Debug)]
+pub struct CommentThreadRow {
+    pub comment: comment::Model,
+    pub reply_count: i64,
+}
+
+impl FromQueryResult for CommentThreadRow {
+    fn from_query_result(res: &QueryResult, pre: &str) -> Result<Self, DbErr> {
+        Ok(Self {
+            comment: comment::Model::from_query_result(res, pre)?,
+            reply_count: res.try_get(pre, "reply_count")?,
+        })
+    }
+}
+
+// `position` numbers each comment among its siblings, so every level can be
+// paged independently: the requested level by offset, the levels below it by
+// the first REPLIES_PER_LEVEL replies. Hidden comments are dropped together
+// with their replies unless $2 is set.
+const COMMENT_THREAD_SQL: &str = r#"
+WITH RECURSIVE siblings AS (
+    SELECT c.*, row_number() OVER (PARTITION BY c.parent_id ORDER BY c.id) AS position
+    FROM "comment" c
+    WHERE c.post_id = $1 AND ($2 OR c.hidden_at IS NULL)
+), thread AS (
+    SELECT s.*, 0 AS level
+    FROM siblings s
+    WHERE s.parent_id IS NOT DISTINCT FROM $3 AND s.position > $4 AND s.position <= $4 + $5
+    UNION ALL
+    SELECT s.*, t.level + 1
+    FROM siblings s
+    JOIN thread t ON s.parent_id = t.id
+    WHERE t.level < $6 AND s.position <= $7
+)
+SELECT t.*, (SELECT count(*) FROM siblings r WHERE r.parent_id = t.id) AS reply_count
+FROM thread t
+ORDER BY t.level, t.id
+"#;
+
+/// A post matched by `search_posts`, with its rank and highlighted fragments.
+#[derive(Debug, FromQueryResult)]
+pub struct PostSearchRow {
+    pub id: i32,
+    pub title: String,
+    pub content: String,
+    pub post_status: PostStatus,
+    pub published_at: Option<DateTime<FixedOffset>>,
+    pub scheduled_at: Option<DateTime<FixedOffset>>,
+    pub author_id: i32,
+    pub created_at: DateTime<FixedOffset>,
+    pub updated_at: DateTime<FixedOffset>,
+    pub rank: f32,
+    pub title_highlight: String,
+    pub snippet: String,
+}
+
+// `%fragment%` with LIKE wildcards in the user input escaped.
+fn contains_pattern(fragment: &str) -> LikeExpr {
+    let escaped = fragment.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    LikeExpr::new(format!("%{}%", escaped)).escape('\\')
+}
+
 impl Repository {
     pub fn new(db: DatabaseConnection) -> Self {
         Self { db }
@@ -17,9 +117,56 @@ impl Repository {
         Utc::now().into()
     }
 
-    // Helper function to convert NaiveDateTime to DateTime<Utc>
-    fn to_utc(date: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
-        date
+    /// Runs `select` for one page, ordered by `sort` and then `id_column` so the
+    /// order is total. Cursor pages fetch one extra row to find out whether
+    /// another page follows. Rows are read as `M`, the entity model or a
+    /// narrower row for `select_only` queries.
+    async fn fetch_page<E, M>(
+        &self,
+        select: Select<E>,
+        id_column: E::Column,
+        sort: &[(E::Column, Order)],
+        request: PageRequest,
+        id_of: impl Fn(&M) -> i32,
+    ) -> Result<Page<M>, DbErr>
+    where
+        E: EntityTrait,
+        E::Model: Sync,
+        M: FromQueryResult + Send + Sync,
+    {
+        let total = select.clone().count(&self.db).await?;
+        let per_page = request.per_page();
+
+        let (items, has_more) = match request {
+            PageRequest::Offset { page, .. } => {
+                let items = sort
+                    .iter()
+                    .fold(select, |select, (column, order)| select.order_by(*column, order.clone()))
+                    .order_by_asc(id_column)
+                    .offset(request.offset())
+                    .limit(per_page)
+                    .into_model::<M>()
+                    .all(&self.db)
+                    .await?;
+                (items, page * per_page < total)
+            }
+            PageRequest::Cursor { after, .. } => {
+                let mut items = select
+                    .filter(id_column.gt(after))
+                    .order_by_asc(id_column)
+                    .limit(per_page + 1)
+                    .into_model::<M>()
+                    .all(&self.db)
+                    .await?;
+                let has_more = items.len() as u64 > per_page;
+                items.truncate(per_page as usize);
+                (items, has_more)
+            }
+        };
+
+        // Cursors follow id order, so a sorted page has no usable one.
+        let next_after = if has_more && sort.is_empty() { items.last().map(&id_of) } else { None };
+        Ok(Page { items, total, next_after })
     }
 
     // User operations
@@ -27,8 +174,41 @@ impl Repository {
         user::Entity::find_by_id(id).one(&self.db).await
     }
 
-    pub async fn find_all_users(&self) -> Result<Vec<user::Model>, DbErr> {
-        user::Entity::find().all(&self.db).await
+    fn user_condition(filter: &UserFilter) -> Condition {
+        let mut condition = Condition::all();
+        if let Some(role) = &filter.user_role {
+            condition = condition.add(user::Column::UserRole.eq(role.clone()));
+        }
+        if let Some(fragment) = &filter.email_contains {
+            condition = condition.add(Expr::col((user::Entity, user::Column::Email)).ilike(contains_pattern(fragment)));
+        }
+        if let Some(after) = filter.created_after {
+            condition = condition.add(user::Column::CreatedAt.gte(after));
+        }
+        if let Some(before) = filter.created_before {
+            condition = condition.add(user::Column::CreatedAt.lt(before));
+        }
+        condition
+    }
+
+    pub async fn find_users_page(&self, filter: &UserFilter, request: PageRequest) -> Result<Page<user::Model>, DbErr> {
+        let select = user::Entity::find().filter(Self::user_condition(filter));
+        self.fetch_page(select, user::Column::Id, &filter.sort, request, |user: &user::Model| user.id).await
+    }
+
+    /// `find_users_page` reading only `fields` (and always the id).
+    pub async fn find_user_fields_page(
+        &self,
+        filter: &UserFilter,
+        request: PageRequest,
+        fields: &[user::Column],
+    ) -> Result<Page<UserFieldsDto>, DbErr> {
+        let select = user::Entity::find()
+            .filter(Self::user_condition(filter))
+            .select_only()
+            .column(user::Column::Id)
+            .columns(fields.iter().copied());
+        self.fetch_page(select, user::Column::Id, &filter.sort, request, |user: &UserFieldsDto| user.id).await
     }
 
     pub async fn find_user_by_email(&self, email: &str) -> Result<Option<user::Model>, DbErr> {
@@ -39,12 +219,15 @@ impl Repository {
     }
 
     pub async fn create_user(&self, user_data: UserCreateDto) -> Result<user::Model, DbErr> {
+        let password_hash = hash_password(&user_data.password)
+            .map_err(|err| DbErr::Custom(format!("Failed to hash password: {}", err)))?;
         let now = Utc::now();
         let user = user::ActiveModel {
             email: Set(user_data.email),
             first_name: Set(user_data.first_name),
             last_name: Set(user_data.last_name),
             user_role: Set(user_data.user_role),
+            password_hash: Set(Some(password_hash)),
             created_at: Set(now),
             updated_at: Set(now),
             ..Default::default()
@@ -53,13 +236,45 @@ impl Repository {
         user.insert(&self.db).await
     }
 
-    pub async fn update_user(&self, id: i32, user_data: user::Model) -> Result<user::Model, DbErr> {
+    pub async fn update_user(&self, existing: &user::Model, user_data: UserUpdateDto) -> Result<user::Model, DbErr> {
+        let mut user = user_data.into_active_model(existing.id);
+        Self::reset_verification_on_new_email(&mut user, existing);
+        user.updated_at = Set(Utc::now());
+
+        user.update(&self.db).await
+    }
+
+    /// Only the fields present in `user_data` are written.
+    pub async fn patch_user(&self, existing: &user::Model, user_data: UserPatchDto) -> Result<user::Model, DbErr> {
+        let mut user = user_data.into_active_model(existing.id);
+        Self::reset_verification_on_new_email(&mut user, existing);
+        user.updated_at = Set(Utc::now());
+
+        user.update(&self.db).await
+    }
+
+    // A new address has not been verified yet, whatever the old one was.
+    fn reset_verification_on_new_email(user: &mut user::ActiveModel, existing: &user::Model) {
+        if matches!(&user.email, ActiveValue::Set(email) if *email != existing.email) {
+            user.verified_at = Set(None);
+        }
+    }
+
+    pub async fn set_user_password(&self, id: i32, password_hash: String) -> Result<user::Model, DbErr> {
         let user = user::ActiveModel {
             id: Set(id),
-            email: Set(user_data.email),
-            first_name: Set(user_data.first_name),
-            last_name: Set(user_data.last_name),
-            user_role: Set(user_data.user_role),
+            password_hash: Set(Some(password_hash)),
+            updated_at: Set(Utc::now()),
+            ..Default::default()
+        };
+
+        user.update(&self.db).await
+    }
+
+    pub async fn mark_user_verified(&self, id: i32) -> Result<user::Model, DbErr> {
+        let user = user::ActiveModel {
+            id: Set(id),
+            verified_at: Set(Some(Utc::now())),
             updated_at: Set(Utc::now()),
             ..Default::default()
         };
@@ -67,8 +282,62 @@ impl Repository {
         user.update(&self.db).await
     }
 
+    /// Bumps the failed login counter and returns the new count.
+    pub async fn increment_failed_logins(&self, id: i32) -> Result<i32, DbErr> {
+        let updated = user::Entity::update_many()
+            .col_expr(
+                user::Column::FailedLoginAttempts,
+                Expr::col(user::Column::FailedLoginAttempts).add(1),
+            )
+            .filter(user::Column::Id.eq(id))
+            .exec_with_returning(&self.db)
+            .await?;
+
+        updated
+            .first()
+            .map(|user| user.failed_login_attempts)
+            .ok_or_else(|| DbErr::RecordNotFound(format!("user {}", id)))
+    }
+
+    pub async fn lock_user_until(&self, id: i32, until: DateTime<Utc>) -> Result<UpdateResult, DbErr> {
+        user::Entity::update_many()
+            .col_expr(user::Column::LockedUntil, Expr::value(until))
+            .filter(user::Column::Id.eq(id))
+            .exec(&self.db)
+            .await
+    }
+
+    /// Clears the failure counter and any lock, after a successful login or an admin unlock.
+    pub async fn reset_failed_logins(&self, id: i32) -> Result<user::Model, DbErr> {
+        let user = user::ActiveModel {
+            id: Set(id),
+            failed_login_attempts: Set(0),
+            locked_until: Set(None),
+            ..Default::default()
+        };
+
+        user.update(&self.db).await
+    }
+
+    /// Deletes the user. Their comments are removed the way `delete_comment`
+    /// does it first, so replies by other people keep their thread.
     pub async fn delete_user(&self, id: i32) -> Result<DeleteResult, DbErr> {
-        let user = user::Entity::delete_by_id(id).exec(&self.db).await?;
+        let txn = self.db.begin().await?;
+        let comment_ids: Vec<i32> = comment::Entity::find()
+            .select_only()
+            .column(comment::Column::Id)
+            .filter(comment::Column::AuthorId.eq(id))
+            .filter(comment::Column::DeletedAt.is_null())
+            .order_by_desc(comment::Column::Depth)
+            .into_tuple()
+            .all(&txn)
+            .await?;
+        for comment_id in comment_ids {
+            Self::remove_comment(&txn, comment_id).await?;
+        }
+
+        let user = user::Entity::delete_by_id(id).exec(&txn).await?;
+        txn.commit().await?;
         Ok(user)
     }
 
@@ -77,34 +346,30 @@ impl Repository {
         profile::Entity::find_by_id(id).one(&self.db).await
     }
 
-    pub async fn find_all_profiles(&self) -> Result<Vec<profile::Model>, DbErr> {
-        profile::Entity::find().all(&self.db).await
+    pub async fn find_profiles_page(&self, request: PageRequest) -> Result<Page<profile::Model>, DbErr> {
+        self.fetch_page(profile::Entity::find(), profile::Column::Id, &[], request, |profile: &profile::Model| profile.id).await
     }
 
-    pub async fn create_profile(&self, profile_data: profile::Model) -> Result<profile::Model, DbErr> {
-        let profile = profile::ActiveModel {
-            bio: Set(profile_data.bio),
-            avatar: Set(profile_data.avatar),
-            user_id: Set(profile_data.user_id),
-            phone_number: Set(profile_data.phone_number),
-            birth_date: Set(Self::to_utc(profile_data.birth_date)),
-            ..Default::default()
-        };
+    /// The user's profile. Users normally have at most one; if older data left
+    /// several, the first one created wins.
+    pub async fn find_profile_of_user(&self, user: &user::Model) -> Result<Option<profile::Model>, DbErr> {
+        user.find_related(profile::Entity)
+            .order_by_asc(profile::Column::Id)
+            .one(&self.db)
+            .await
+    }
 
-        profile.insert(&self.db).await
+    /// Fails with a unique violation if the user already has a profile.
+    pub async fn create_profile(&self, user_id: i32, profile_data: ProfileCreateDto) -> Result<profile::Model, DbErr> {
+        profile_data.into_active_model(user_id).insert(&self.db).await
     }
 
-    pub async fn update_profile(&self, id: i32, profile_data: profile::Model) -> Result<profile::Model, DbErr> {
-        let profile = profile::ActiveModel {
-            id: Set(id),
-            bio: Set(profile_data.bio),
-            avatar: Set(profile_data.avatar),
-            user_id: Set(profile_data.user_id),
-            phone_number: Set(profile_data.phone_number),
-            birth_date: Set(Self::to_utc(profile_data.birth_date)),
-        };
+    pub async fn update_profile(&self, id: i32, profile_data: ProfileUpdateDto) -> Result<profile::Model, DbErr> {
+        profile_data.into_active_model(id).update(&self.db).await
+    }
 
-        profile.update(&self.db).await
+    pub async fn patch_profile(&self, id: i32, profile_data: ProfilePatchDto) -> Result<profile::Model, DbErr> {
+        profile_data.into_active_model(id).update(&self.db).await
     }
 
     pub async fn delete_profile(&self, id: i32) -> Result<DeleteResult, DbErr> {
@@ -117,41 +382,715 @@ impl Repository {
         post::Entity::find_by_id(id).one(&self.db).await
     }
 
-    pub async fn find_all_posts(&self) -> Result<Vec<post::Model>, DbErr> {
-        post::Entity::find().all(&self.db).await
+    fn visibility_condition(visibility: PostVisibility) -> Condition {
+        let published = post::Column::PostStatus.eq(PostStatus::Published);
+        match visibility {
+            PostVisibility::All => Condition::all(),
+            PostVisibility::Published => Condition::all().add(published),
+            PostVisibility::PublishedOrAuthor(user_id) => Condition::any().add(published).add(post::Column::AuthorId.eq(user_id)),
+        }
+    }
+
+    fn post_condition(filter: &PostFilter) -> Condition {
+        let mut condition = Condition::all().add(Self::visibility_condition(filter.visibility));
+        if let Some(author_id) = filter.author_id {
+            condition = condition.add(post::Column::AuthorId.eq(author_id));
+        }
+        if let Some(status) = &filter.post_status {
+            condition = condition.add(post::Column::PostStatus.eq(status.clone()));
+        }
+        if let Some(after) = filter.created_after {
+            condition = condition.add(post::Column::CreatedAt.gte(after));
+        }
+        if let Some(before) = filter.created_before {
+            condition = condition.add(post::Column::CreatedAt.lt(before));
+        }
+        condition
+    }
+
+    pub async fn find_posts_page(&self, scope: PostScope<'_>, filter: &PostFilter, request: PageRequest) -> Result<Page<post::Model>, DbErr> {
+        let select = scope.select().filter(Self::post_condition(filter));
+        self.fetch_page(select, post::Column::Id, &filter.sort, request, |post: &post::Model| post.id).await
+    }
+
+    /// `find_posts_page` reading only `fields` (and always the id).
+    pub async fn find_post_fields_page(
+        &self,
+        scope: PostScope<'_>,
+        filter: &PostFilter,
+        request: PageRequest,
+        fields: &[post::Column],
+    ) -> Result<Page<PostFieldsDto>, DbErr> {
+        let select = scope
+            .select()
+            .filter(Self::post_condition(filter))
+            .select_only()
+            .column(post::Column::Id)
+            .columns(fields.iter().copied());
+        self.fetch_page(select, post::Column::Id, &filter.sort, request, |post: &PostFieldsDto| post.id).await
+    }
+
+    /// Full-text search over title and content. `text` is parsed with
+    /// `websearch_to_tsquery`, so quotes, `or` and `-word` work as on search
+    /// engines. Ranked best first unless `filter.sort` says otherwise.
+    pub async fn search_posts(&self, text: &str, filter: &PostFilter, request: PageRequest) -> Result<Page<PostSearchRow>, DbErr> {
+        let per_page = match request {
+            PageRequest::Offset { per_page, .. } => per_page,
+            PageRequest::Cursor { .. } => {
+                return Err(DbErr::Custom("search results cannot be paged with a cursor".to_string()))
+            }
+        };
+
+        let select = post::Entity::find()
+            .filter(Self::post_condition(filter))
+            .filter(Expr::cust_with_values(
+                r#""post"."search_vector" @@ websearch_to_tsquery('english', $1)"#,
+                [text],
+            ));
+        let total = select.clone().count(&self.db).await?;
+
+        let select = select
+            .column_as(
+                Expr::cust_with_values(r#"ts_rank("post"."search_vector", websearch_to_tsquery('english', $1))"#, [text]),
+                "rank",
+            )
+            .column_as(
+                Expr::cust_with_values(
+                    r#"ts_headline('english', "post"."title", websearch_to_tsquery('english', $1), $2)"#,
+                    [text, "StartSel=<mark>, StopSel=</mark>, HighlightAll=true"],
+                ),
+                "title_highlight",
+            )
+            .column_as(
+                Expr::cust_with_values(
+                    r#"ts_headline('english', "post"."content", websearch_to_tsquery('english', $1), $2)"#,
+                    [text, "StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=30, MinWords=10"],
+                ),
+                "snippet",
+            );
+        let select = if filter.sort.is_empty() {
+            select.order_by_desc(Expr::cust("rank"))
+        } else {
+            filter
+                .sort
+                .iter()
+                .fold(select, |select, (column, order)| select.order_by(*column, order.clone()))
+        };
+
+        let items = select
+            .order_by_asc(post::Column::Id)
+            .offset(request.offset())
+            .limit(per_page)
+            .into_model::<PostSearchRow>()
+            .all(&self.db)
+            .await?;
+
+        Ok(Page { items, total, next_after: None })
+    }
+
+    pub async fn create_post(&self, author_id: i32, post_data: PostCreateDto) -> Result<post::Model, DbErr> {
+        let now = Self::now();
+        let mut post = post_data.into_active_model(author_id);
+        post.created_at = Set(now);
+        post.updated_at = Set(now);
+
+        post.insert(&self.db).await
+    }
+
+    pub async fn update_post(&self, id: i32, post_data: PostUpdateDto) -> Result<post::Model, DbErr> {
+        let mut post = post_data.into_active_model(id);
+        post.updated_at = Set(Self::now());
+
+        post.update(&self.db).await
+    }
+
+    pub async fn patch_post(&self, id: i32, post_data: PostPatchDto) -> Result<post::Model, DbErr> {
+        let mut post = post_data.into_active_model(id);
+        post.updated_at = Set(Self::now());
+
+        post.update(&self.db).await
+    }
+
+    /// Moves the post to `to` if its status is one of `from`. The check is
+    /// part of the update, so of two racing transitions only one applies;
+    /// `None` means the post was not in a state it can leave that way.
+    /// Publishing stamps `published_at`, going back to draft clears it. Any
+    /// transition cancels a pending schedule.
+    pub async fn transition_post(&self, id: i32, from: &[PostStatus], to: PostStatus) -> Result<Option<post::Model>, DbErr> {
+        let now = Self::now();
+        let published_at = match to {
+            PostStatus::Published => Some(Some(now)),
+            PostStatus::Draft => Some(None),
+            PostStatus::Archived => None,
+        };
+
+        let mut update = post::Entity::update_many()
+            .col_expr(post::Column::PostStatus, Expr::val(to).as_enum(PostStatus::name()))
+            .col_expr(post::Column::ScheduledAt, Expr::value(None::<DateTime<FixedOffset>>))
+            .col_expr(post::Column::UpdatedAt, Expr::value(now));
+        if let Some(published_at) = published_at {
+            update = update.col_expr(post::Column::PublishedAt, Expr::value(published_at));
+        }
+
+        let posts = update
+            .filter(post::Column::Id.eq(id))
+            .filter(post::Column::PostStatus.is_in(from.iter().cloned()))
+            .exec_with_returning(&self.db)
+            .await?;
+        Ok(posts.into_iter().next())
     }
 
-    pub async fn create_post(&self, post_data: post::Model) -> Result<post::Model, DbErr> {
+    /// Sets or clears the time a draft goes live. `None` if the post is not a
+    /// draft.
+    pub async fn schedule_post(&self, id: i32, at: Option<DateTime<Utc>>) -> Result<Option<post::Model>, DbErr> {
+        let posts = post::Entity::update_many()
+            .col_expr(post::Column::ScheduledAt, Expr::value(at.map(|at| at.fixed_offset())))
+            .col_expr(post::Column::UpdatedAt, Expr::value(Self::now()))
+            .filter(post::Column::Id.eq(id))
+            .filter(post::Column::PostStatus.eq(PostStatus::Draft))
+            .exec_with_returning(&self.db)
+            .await?;
+        Ok(posts.into_iter().next())
+    }
+
+    /// Publishes up to `limit` drafts whose `scheduled_at` has passed, oldest
+    /// schedule first. Rows are claimed with `FOR UPDATE SKIP LOCKED`, so
+    /// instances running this at the same time never get the same post.
+    pub async fn publish_due_posts(&self, limit: u64) -> Result<Vec<post::Model>, DbErr> {
         let now = Self::now();
-        let post = post::ActiveModel {
-            title: Set(post_data.title),
-            content: Set(post_data.content),
-            published: Set(post_data.published),
-            author_id: Set(post_data.author_id),
+        let txn = self.db.begin().await?;
+
+        let ids: Vec<i32> = post::Entity::find()
+            .select_only()
+            .column(post::Column::Id)
+            .filter(post::Column::PostStatus.eq(PostStatus::Draft))
+            .filter(post::Column::ScheduledAt.lte(now))
+            .order_by_asc(post::Column::ScheduledAt)
+            .limit(limit)
+            .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+            .into_tuple()
+            .all(&txn)
+            .await?;
+        if ids.is_empty() {
+            txn.commit().await?;
+            return Ok(Vec::new());
+        }
+
+        let posts = post::Entity::update_many()
+            .col_expr(post::Column::PostStatus, Expr::val(PostStatus::Published).as_enum(PostStatus::name()))
+            .col_expr(post::Column::PublishedAt, Expr::value(now))
+            .col_expr(post::Column::ScheduledAt, Expr::value(None::<DateTime<FixedOffset>>))
+            .col_expr(post::Column::UpdatedAt, Expr::value(now))
+            .filter(post::Column::Id.is_in(ids))
+            .exec_with_returning(&txn)
+            .await?;
+
+        txn.commit().await?;
+        Ok(posts)
+    }
+
+    pub async fn delete_post(&self, id: i32) -> Result<DeleteResult, DbErr> {
+        let post = post::Entity::delete_by_id(id).exec(&self.db).await?;
+        Ok(post)
+    }
+
+    // Category operations
+    pub async fn find_category_by_id(&self, id: i32) -> Result<Option<category::Model>, DbErr> {
+        category::Entity::find_by_id(id).one(&self.db).await
+    }
+
+    pub async fn find_categories_page(&self, request: PageRequest) -> Result<Page<category::Model>, DbErr> {
+        self.fetch_page(category::Entity::find(), category::Column::Id, &[], request, |category: &category::Model| category.id)
+            .await
+    }
+
+    pub async fn create_category(&self, category_data: CategoryCreateDto) -> Result<category::Model, DbErr> {
+        let now = Self::now();
+        let mut category = category_data.into_active_model();
+        category.created_at = Set(now);
+        category.updated_at = Set(now);
+
+        category.insert(&self.db).await
+    }
+
+    pub async fn update_category(&self, id: i32, category_data: CategoryUpdateDto) -> Result<category::Model, DbErr> {
+        let mut category = category_data.into_active_model(id);
+        category.updated_at = Set(Self::now());
+
+        category.update(&self.db).await
+    }
+
+    pub async fn patch_category(&self, id: i32, category_data: CategoryPatchDto) -> Result<category::Model, DbErr> {
+        let mut category = category_data.into_active_model(id);
+        category.updated_at = Set(Self::now());
+
+        category.update(&self.db).await
+    }
+
+    pub async fn delete_category(&self, id: i32) -> Result<DeleteResult, DbErr> {
+        category::Entity::delete_by_id(id).exec(&self.db).await
+    }
+
+    pub async fn find_categories_of_post(&self, post: &post::Model) -> Result<Vec<category::Model>, DbErr> {
+        post.find_related(category::Entity)
+            .order_by_asc(category::Column::CategoryName)
+            .all(&self.db)
+            .await
+    }
+
+    /// Links the post to the category. Assigning twice is not an error.
+    pub async fn assign_category(&self, post_id: i32, category_id: i32) -> Result<(), DbErr> {
+        let link = post_category::ActiveModel {
+            post_id: Set(post_id),
+            category_id: Set(category_id),
+        };
+        post_category::Entity::insert(link)
+            .on_conflict(
+                sea_query::OnConflict::columns([post_category::Column::PostId, post_category::Column::CategoryId])
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec_without_returning(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn unassign_category(&self, post_id: i32, category_id: i32) -> Result<DeleteResult, DbErr> {
+        post_category::Entity::delete_by_id((post_id, category_id)).exec(&self.db).await
+    }
+
+    // Comment operations
+    pub async fn find_comment_by_id(&self, id: i32) -> Result<Option<comment::Model>, DbErr> {
+        comment::Entity::find_by_id(id).one(&self.db).await
+    }
+
+    /// Comments on `post`, oldest first.
+    pub async fn find_comments_page(
+        &self,
+        post: &post::Model,
+        include_hidden: bool,
+        request: PageRequest,
+    ) -> Result<Page<comment::Model>, DbErr> {
+        let mut select = post.find_related(comment::Entity);
+        if !include_hidden {
+            select = select.filter(comment::Column::HiddenAt.is_null());
+        }
+        self.fetch_page(select, comment::Column::Id, &[], request, |comment: &comment::Model| comment.id).await
+    }
+
+    /// One page of the comments directly under `parent` (the post's top level
+    /// when `None`), each with up to THREAD_LEVELS levels of replies.
+    pub async fn find_comment_thread(
+        &self,
+        post_id: i32,
+        parent: Option<i32>,
+        include_hidden: bool,
+        request: PageRequest,
+    ) -> Result<Page<CommentThreadRow>, DbErr> {
+        let per_page = match request {
+            PageRequest::Offset { per_page, .. } => per_page,
+            PageRequest::Cursor { .. } => {
+                return Err(DbErr::Custom("comment threads cannot be paged with a cursor".to_string()))
+            }
+        };
+
+        let mut level = comment::Entity::find()
+            .filter(comment::Column::PostId.eq(post_id))
+            .filter(match parent {
+                Some(parent) => comment::Column::ParentId.eq(parent),
+                None => comment::Column::ParentId.is_null(),
+            });
+        if !include_hidden {
+            level = level.filter(comment::Column::HiddenAt.is_null());
+        }
+        let total = level.count(&self.db).await?;
+
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            COMMENT_THREAD_SQL,
+            [
+                post_id.into(),
+                include_hidden.into(),
+                parent.into(),
+                (request.offset() as i64).into(),
+                (per_page as i64).into(),
+                THREAD_LEVELS.into(),
+                REPLIES_PER_LEVEL.into(),
+            ],
+        );
+        let items = CommentThreadRow::find_by_statement(statement).all(&self.db).await?;
+
+        Ok(Page { items, total, next_after: None })
+    }
+
+    pub async fn create_comment(
+        &self,
+        post_id: i32,
+        author_id: i32,
+        depth: i32,
+        comment_data: CommentCreateDto,
+    ) -> Result<comment::Model, DbErr> {
+        let now = Self::now();
+        let mut comment = comment_data.into_active_model(post_id, author_id, depth);
+        comment.created_at = Set(now);
+        comment.updated_at = Set(now);
+
+        comment.insert(&self.db).await
+    }
+
+    pub async fn update_comment(&self, id: i32, comment_data: CommentUpdateDto) -> Result<comment::Model, DbErr> {
+        let mut comment = comment_data.into_active_model(id);
+        comment.updated_at = Set(Self::now());
+
+        comment.update(&self.db).await
+    }
+
+    pub async fn set_comment_hidden(&self, id: i32, hidden: bool) -> Result<comment::Model, DbErr> {
+        let comment = comment::ActiveModel {
+            id: Set(id),
+            hidden_at: Set(hidden.then(Self::now)),
+            ..Default::default()
+        };
+
+        comment.update(&self.db).await
+    }
+
+    /// Deletes the comment. A comment with replies is kept as a blanked-out
+    /// placeholder instead, so the thread below it survives; placeholders
+    /// left without replies are removed too. Row locks make a reply posted at
+    /// the same time wait for the decision.
+    pub async fn delete_comment(&self, id: i32) -> Result<(), DbErr> {
+        let txn = self.db.begin().await?;
+        Self::remove_comment(&txn, id).await?;
+        txn.commit().await
+    }
+
+    async fn remove_comment(txn: &DatabaseTransaction, id: i32) -> Result<(), DbErr> {
+        let mut target = comment::Entity::find_by_id(id).lock_exclusive().one(txn).await?;
+
+        while let Some(comment) = target {
+            if Self::has_replies(txn, comment.id).await? {
+                if comment.deleted_at.is_none() {
+                    let placeholder = comment::ActiveModel {
+                        id: Set(comment.id),
+                        content: Set(String::new()),
+                        deleted_at: Set(Some(Self::now())),
+                        ..Default::default()
+                    };
+                    placeholder.update(txn).await?;
+                }
+                break;
+            }
+
+            comment::Entity::delete_by_id(comment.id).exec(txn).await?;
+            target = match comment.parent_id {
+                Some(parent_id) => comment::Entity::find_by_id(parent_id)
+                    .lock_exclusive()
+                    .one(txn)
+                    .await?
+                    .filter(|parent| parent.deleted_at.is_some()),
+                None => None,
+            };
+        }
+
+        Ok(())
+    }
+
+    async fn has_replies(db: &impl ConnectionTrait, id: i32) -> Result<bool, DbErr> {
+        let replies = comment::Entity::find()
+            .filter(comment::Column::ParentId.eq(id))
+            .count(db)
+            .await?;
+        Ok(replies > 0)
+    }
+
+    /// Number of visible comments per post (replies included, deleted
+    /// placeholders not), in one query. Posts without
+    /// comments are missing from the map.
+    pub async fn count_visible_comments(&self, post_ids: &[i32]) -> Result<HashMap<i32, u64>, DbErr> {
+        if post_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let counts: Vec<(i32, i64)> = comment::Entity::find()
+            .select_only()
+            .column(comment::Column::PostId)
+            .column_as(comment::Column::Id.count(), "comment_count")
+            .filter(comment::Column::PostId.is_in(post_ids.iter().copied()))
+            .filter(comment::Column::HiddenAt.is_null())
+            .filter(comment::Column::DeletedAt.is_null())
+            .group_by(comment::Column::PostId)
+            .into_tuple()
+            .all(&self.db)
+            .await?;
+        Ok(counts.into_iter().map(|(post_id, count)| (post_id, count as u64)).collect())
+    }
+
+    // Relation loading for `?include=`. Each call runs a single query for the
+    // whole slice and returns one entry per input row, in the same order.
+    pub async fn load_post_authors(&self, posts: &[post::Model]) -> Result<Vec<Option<user::Model>>, DbErr> {
+        posts.load_one(user::Entity, &self.db).await
+    }
+
+    pub async fn load_user_profiles(&self, users: &[user::Model]) -> Result<Vec<Option<profile::Model>>, DbErr> {
+        let profiles = users.load_many(profile::Entity, &self.db).await?;
+        Ok(profiles
+            .into_iter()
+            .map(|profiles| profiles.into_iter().min_by_key(|profile| profile.id))
+            .collect())
+    }
+
+    pub async fn load_user_posts(&self, users: &[user::Model], visibility: PostVisibility) -> Result<Vec<Vec<post::Model>>, DbErr> {
+        let mut posts = users
+            .load_many(post::Entity::find().filter(Self::visibility_condition(visibility)), &self.db)
+            .await?;
+        for posts in &mut posts {
+            posts.sort_by_key(|post| post.id);
+        }
+        Ok(posts)
+    }
+
+    // Refresh token operations
+    pub async fn create_refresh_token(
+        &self,
+        user_id: i32,
+        family_id: Uuid,
+        token_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<refresh_token::Model, DbErr> {
+        let token = refresh_token::ActiveModel {
+            user_id: Set(user_id),
+            family_id: Set(family_id),
+            token_hash: Set(token_hash),
+            expires_at: Set(expires_at.into()),
+            revoked_at: Set(None),
+            created_at: Set(Self::now()),
+            ..Default::default()
+        };
+
+        token.insert(&self.db).await
+    }
+
+    pub async fn find_refresh_token_by_hash(&self, token_hash: &str) -> Result<Option<refresh_token::Model>, DbErr> {
+        refresh_token::Entity::find()
+            .filter(refresh_token::Column::TokenHash.eq(token_hash))
+            .one(&self.db)
+            .await
+    }
+
+    /// Revokes `current` and issues its successor in the same family. Returns `None`
+    /// when `current` was already revoked by a concurrent request.
+    pub async fn rotate_refresh_token(
+        &self,
+        current: &refresh_token::Model,
+        token_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<refresh_token::Model>, DbErr> {
+        let txn = self.db.begin().await?;
+        let now = Self::now();
+
+        let revoked = refresh_token::Entity::update_many()
+            .col_expr(refresh_token::Column::RevokedAt, Expr::value(now))
+            .filter(refresh_token::Column::Id.eq(current.id))
+            .filter(refresh_token::Column::RevokedAt.is_null())
+            .exec(&txn)
+            .await?;
+        if revoked.rows_affected == 0 {
+            txn.rollback().await?;
+            return Ok(None);
+        }
+
+        let next = refresh_token::ActiveModel {
+            user_id: Set(current.user_id),
+            family_id: Set(current.family_id),
+            token_hash: Set(token_hash),
+            expires_at: Set(expires_at.into()),
+            revoked_at: Set(None),
             created_at: Set(now),
-            updated_at: Set(now),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        txn.commit().await?;
+        Ok(Some(next))
+    }
+
+    pub async fn revoke_refresh_token_family(&self, family_id: Uuid) -> Result<UpdateResult, DbErr> {
+        refresh_token::Entity::update_many()
+            .col_expr(refresh_token::Column::RevokedAt, Expr::value(Self::now()))
+            .filter(refresh_token::Column::FamilyId.eq(family_id))
+            .filter(refresh_token::Column::RevokedAt.is_null())
+            .exec(&self.db)
+            .await
+    }
+
+    pub async fn revoke_user_refresh_tokens(&self, user_id: i32) -> Result<UpdateResult, DbErr> {
+        refresh_token::Entity::update_many()
+            .col_expr(refresh_token::Column::RevokedAt, Expr::value(Self::now()))
+            .filter(refresh_token::Column::UserId.eq(user_id))
+            .filter(refresh_token::Column::RevokedAt.is_null())
+            .exec(&self.db)
+            .await
+    }
+
+    // Single-use user token operations (email verification, password reset)
+    pub async fn create_user_token(
+        &self,
+        user_id: i32,
+        purpose: UserTokenPurpose,
+        token_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<user_token::Model, DbErr> {
+        let token = user_token::ActiveModel {
+            user_id: Set(user_id),
+            purpose: Set(purpose),
+            token_hash: Set(token_hash),
+            expires_at: Set(expires_at.into()),
+            used_at: Set(None),
+            created_at: Set(Self::now()),
             ..Default::default()
         };
 
-        post.insert(&self.db).await
+        token.insert(&self.db).await
+    }
+
+    /// Finds a token that is neither expired nor used.
+    pub async fn find_live_user_token(
+        &self,
+        token_hash: &str,
+        purpose: UserTokenPurpose,
+    ) -> Result<Option<user_token::Model>, DbErr> {
+        user_token::Entity::find()
+            .filter(user_token::Column::TokenHash.eq(token_hash))
+            .filter(user_token::Column::Purpose.eq(purpose))
+            .filter(user_token::Column::UsedAt.is_null())
+            .filter(user_token::Column::ExpiresAt.gt(Self::now()))
+            .one(&self.db)
+            .await
+    }
+
+    /// Marks the token as used. Returns `false` when a concurrent request got there
+    /// first, so each token works exactly once.
+    pub async fn use_user_token(&self, id: i32) -> Result<bool, DbErr> {
+        let used = user_token::Entity::update_many()
+            .col_expr(user_token::Column::UsedAt, Expr::value(Self::now()))
+            .filter(user_token::Column::Id.eq(id))
+            .filter(user_token::Column::UsedAt.is_null())
+            .exec(&self.db)
+            .await?;
+
+        Ok(used.rows_affected == 1)
     }
 
-    pub async fn update_post(&self, id: i32, post_data: post::Model) -> Result<post::Model, DbErr> {
-        let post = post::ActiveModel {
+    /// Invalidates every outstanding token of `purpose` for the user.
+    pub async fn expire_user_tokens(&self, user_id: i32, purpose: UserTokenPurpose) -> Result<UpdateResult, DbErr> {
+        user_token::Entity::update_many()
+            .col_expr(user_token::Column::UsedAt, Expr::value(Self::now()))
+            .filter(user_token::Column::UserId.eq(user_id))
+            .filter(user_token::Column::Purpose.eq(purpose))
+            .filter(user_token::Column::UsedAt.is_null())
+            .exec(&self.db)
+            .await
+    }
+
+    // Two-factor authentication operations
+    /// Stores a pending TOTP secret; 2FA stays off until `enable_user_totp`.
+    pub async fn set_pending_totp_secret(&self, id: i32, secret: String) -> Result<user::Model, DbErr> {
+        let user = user::ActiveModel {
             id: Set(id),
-            title: Set(post_data.title),
-            content: Set(post_data.content),
-            published: Set(post_data.published),
-            author_id: Set(post_data.author_id),
-            updated_at: Set(Self::now()),
+            totp_secret: Set(Some(secret)),
+            totp_enabled_at: Set(None),
+            totp_last_used_step: Set(None),
+            updated_at: Set(Utc::now()),
             ..Default::default()
         };
 
-        post.update(&self.db).await
+        user.update(&self.db).await
     }
 
-    pub async fn delete_post(&self, id: i32) -> Result<DeleteResult, DbErr> {
-        let post = post::Entity::delete_by_id(id).exec(&self.db).await?;
-        Ok(post)
+    /// Turns 2FA on and replaces any previous recovery codes with `code_hashes`.
+    pub async fn enable_user_totp(&self, id: i32, code_hashes: Vec<String>) -> Result<user::Model, DbErr> {
+        let txn = self.db.begin().await?;
+
+        recovery_code::Entity::delete_many()
+            .filter(recovery_code::Column::UserId.eq(id))
+            .exec(&txn)
+            .await?;
+
+        let now = Self::now();
+        let codes = code_hashes.into_iter().map(|code_hash| recovery_code::ActiveModel {
+            user_id: Set(id),
+            code_hash: Set(code_hash),
+            used_at: Set(None),
+            created_at: Set(now),
+            ..Default::default()
+        });
+        recovery_code::Entity::insert_many(codes).exec(&txn).await?;
+
+        let user = user::ActiveModel {
+            id: Set(id),
+            totp_enabled_at: Set(Some(Utc::now())),
+            updated_at: Set(Utc::now()),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await?;
+
+        txn.commit().await?;
+        Ok(user)
+    }
+
+    pub async fn disable_user_totp(&self, id: i32) -> Result<user::Model, DbErr> {
+        let txn = self.db.begin().await?;
+
+        recovery_code::Entity::delete_many()
+            .filter(recovery_code::Column::UserId.eq(id))
+            .exec(&txn)
+            .await?;
+
+        let user = user::ActiveModel {
+            id: Set(id),
+            totp_secret: Set(None),
+            totp_enabled_at: Set(None),
+            totp_last_used_step: Set(None),
+            updated_at: Set(Utc::now()),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await?;
+
+        txn.commit().await?;
+        Ok(user)
+    }
+
+    /// Records the time step of an accepted TOTP code. Returns `false` if that
+    /// step (or a later one) was already used, which blocks code replay.
+    pub async fn record_totp_step(&self, id: i32, step: i64) -> Result<bool, DbErr> {
+        let updated = user::Entity::update_many()
+            .col_expr(user::Column::TotpLastUsedStep, Expr::value(step))
+            .filter(user::Column::Id.eq(id))
+            .filter(
+                Condition::any()
+                    .add(user::Column::TotpLastUsedStep.is_null())
+                    .add(user::Column::TotpLastUsedStep.lt(step)),
+            )
+            .exec(&self.db)
+            .await?;
+
+        Ok(updated.rows_affected == 1)
+    }
+
+    /// Burns an unused recovery code. Returns `false` if no such code is left.
+    pub async fn use_recovery_code(&self, user_id: i32, code_hash: &str) -> Result<bool, DbErr> {
+        let used = recovery_code::Entity::update_many()
+            .col_expr(recovery_code::Column::UsedAt, Expr::value(Self::now()))
+            .filter(recovery_code::Column::UserId.eq(user_id))
+            .filter(recovery_code::Column::CodeHash.eq(code_hash))
+            .filter(recovery_code::Column::UsedAt.is_null())
+            .exec(&self.db)
+            .await?;
+
+        Ok(used.rows_affected > 0)
     }
 }
@@ -0,0 +1,49 @@
+use std::env;
+use std::time::Duration;
+
+use sea_orm::{DatabaseConnection, DbErr};
+use tokio::task::JoinHandle;
+use tokio::time::{self, MissedTickBehavior};
+
+use crate::repository::Repository;
+
+/// Posts published per transaction. A larger backlog is worked off in
+/// several rounds within the same tick.
+const BATCH_SIZE: u64 = 50;
+
+/// Starts the background task that publishes scheduled posts once they are
+/// due. It checks every `PUBLISH_POLL_SECONDS` (default 30). Every server
+/// instance may run one; the repository makes sure each post is published
+/// exactly once.
+pub fn spawn_publisher(db: DatabaseConnection) -> JoinHandle<()> {
+    let seconds = env::var("PUBLISH_POLL_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|seconds| *seconds > 0)
+        .unwrap_or(30);
+
+    tokio::spawn(async move {
+        let repo = Repository::new(db);
+        let mut ticker = time::interval(Duration::from_secs(seconds));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            if let Err(err) = publish_due_posts(&repo).await {
+                log::error!("Publishing scheduled posts failed: {}", err);
+            }
+        }
+    })
+}
+
+async fn publish_due_posts(repo: &Repository) -> Result<(), DbErr> {
+    loop {
+        let posts = repo.publish_due_posts(BATCH_SIZE).await?;
+        for post in &posts {
+            log::info!("Published scheduled post {} by user {}", post.id, post.author_id);
+        }
+        if (posts.len() as u64) < BATCH_SIZE {
+            return Ok(());
+        }
+    }
+}
@@ -1,7 +1,17 @@
-use actix_web::{web, App, HttpServer};
+use actix_web::{middleware, web, App, HttpServer};
 use sea_orm::DatabaseConnection;
 use std::env;
 use crate::api::*;
+use crate::account;
+use crate::auth::{self, AuthSettings};
+use crate::error::AppError;
+use crate::jwt;
+use crate::login_throttle::{LockoutPolicy, LoginThrottle};
+use crate::mailer::{mailer_from_env, Mailer};
+use crate::permissions::{require_role, AdminOnly};
+use crate::repository::Repository;
+use crate::scheduler;
+use crate::two_factor;
 
 pub async fn start_server(db: DatabaseConnection) -> std::io::Result<()> {
     let host = env::var("SERVER_HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
@@ -11,33 +21,100 @@ pub async fn start_server(db: DatabaseConnection) -> std::io::Result<()> {
         .parse::<usize>()
         .unwrap_or_else(|_| num_cpus::get());
 
+    let auth_settings = AuthSettings::from_env();
+    // Load the JWT keys now so a bad configuration fails at startup, not on the first login.
+    jwt::keys();
+    let mailer: web::Data<dyn Mailer> = web::Data::from(mailer_from_env());
+    // Shared by all workers so the per-address counters are not split between them.
+    let login_throttle = web::Data::new(LoginThrottle::new(LockoutPolicy::from_env()));
+
+    scheduler::spawn_publisher(db.clone());
+
     println!("Starting server at http://{}:{}", host, port);
     
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(db.clone()))
-            .wrap(actix_web::middleware::Logger::default())
-            .wrap(actix_web::middleware::Compress::default())
+            .app_data(web::Data::new(Repository::new(db.clone())))
+            .app_data(web::Data::new(auth_settings.clone()))
+            .app_data(mailer.clone())
+            .app_data(login_throttle.clone())
+            // Malformed bodies and path segments get the same JSON envelope as every other error.
+            .app_data(web::JsonConfig::default().error_handler(|err, _| AppError::validation(err.to_string()).into()))
+            .app_data(web::PathConfig::default().error_handler(|err, _| AppError::validation(err.to_string()).into()))
+            .app_data(web::QueryConfig::default().error_handler(|err, _| AppError::validation(err.to_string()).into()))
+            .wrap(middleware::Logger::default())
+            .wrap(middleware::Compress::default())
+            .route("/.well-known/jwks.json", web::get().to(jwt::jwks))
             .service(
                 web::scope("/api")
+                    .service(web::scope("/auth")
+                        .route("/register", web::post().to(auth::register))
+                        .route("/login", web::post().to(auth::login))
+                        .route("/login/2fa", web::post().to(two_factor::verify_login))
+                        .route("/refresh", web::post().to(auth::refresh))
+                        .route("/logout", web::post().to(auth::logout))
+                        .route("/logout-all", web::post().to(auth::logout_all))
+                        .route("/verify-email/request", web::post().to(account::request_email_verification))
+                        .route("/verify-email/confirm", web::post().to(account::confirm_email_verification))
+                        .route("/password-reset/request", web::post().to(account::request_password_reset))
+                        .route("/password-reset/confirm", web::post().to(account::confirm_password_reset))
+                        .route("/2fa/setup", web::post().to(two_factor::setup))
+                        .route("/2fa/confirm", web::post().to(two_factor::confirm))
+                        .route("/2fa/disable", web::post().to(two_factor::disable)))
                     .service(web::scope("/users")
                         .route("", web::get().to(get_users))
                         .route("", web::post().to(create_user))
                         .route("/{id}", web::get().to(get_user))
                         .route("/{id}", web::put().to(update_user))
-                        .route("/{id}", web::delete().to(delete_user)))
+                        .route("/{id}", web::patch().to(patch_user))
+                        .route("/{id}", web::delete().to(delete_user))
+                        .route("/{id}/posts", web::get().to(get_user_posts))
+                        .route("/{id}/posts", web::post().to(create_user_post))
+                        .route("/{id}/profile", web::get().to(get_user_profile)))
                     .service(web::scope("/profiles")
                         .route("", web::get().to(get_profiles))
                         .route("", web::post().to(create_profile))
                         .route("/{id}", web::get().to(get_profile))
                         .route("/{id}", web::put().to(update_profile))
+                        .route("/{id}", web::patch().to(patch_profile))
                         .route("/{id}", web::delete().to(delete_profile)))
                     .service(web::scope("/posts")
                         .route("", web::get().to(get_posts))
                         .route("", web::post().to(create_post))
+                        .route("/search", web::get().to(search_posts))
                         .route("/{id}", web::get().to(get_post))
                         .route("/{id}", web::put().to(update_post))
-                        .route("/{id}", web::delete().to(delete_post)))
+                        .route("/{id}", web::patch().to(patch_post))
+                        .route("/{id}", web::delete().to(delete_post))
+                        .route("/{id}/publish", web::post().to(publish_post))
+                        .route("/{id}/archive", web::post().to(archive_post))
+                        .route("/{id}/unpublish", web::post().to(unpublish_post))
+                        .route("/{id}/schedule", web::post().to(schedule_post))
+                        .route("/{id}/schedule", web::delete().to(unschedule_post))
+                        .route("/{id}/comments", web::get().to(get_post_comments))
+                        .route("/{id}/comments", web::post().to(create_comment))
+                        .route("/{id}/comments/tree", web::get().to(get_comment_thread))
+                        .route("/{id}/categories", web::get().to(get_post_categories))
+                        .route("/{id}/categories/{category_id}", web::put().to(assign_post_category))
+                        .route("/{id}/categories/{category_id}", web::delete().to(unassign_post_category)))
+                    .service(web::scope("/comments")
+                        .route("/{id}", web::put().to(update_comment))
+                        .route("/{id}", web::delete().to(delete_comment))
+                        .route("/{id}/replies", web::get().to(get_comment_replies))
+                        .route("/{id}/hide", web::post().to(hide_comment))
+                        .route("/{id}/unhide", web::post().to(unhide_comment)))
+                    .service(web::scope("/categories")
+                        .route("", web::get().to(get_categories))
+                        .route("", web::post().to(create_category))
+                        .route("/{id}", web::get().to(get_category))
+                        .route("/{id}", web::put().to(update_category))
+                        .route("/{id}", web::patch().to(patch_category))
+                        .route("/{id}", web::delete().to(delete_category))
+                        .route("/{id}/posts", web::get().to(get_category_posts)))
+                    .service(web::scope("/admin")
+                        .wrap(middleware::from_fn(require_role::<AdminOnly>))
+                        .route("/users/{id}/unlock", web::post().to(unlock_user)))
             )
     })
     .workers(workers)
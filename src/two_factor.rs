@@ -0,0 +1,360 @@
+use actix_web::{web, FromRequest, HttpRequest};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{Duration, Utc};
+use futures::future::{ready, Ready};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::auth::{
+    bearer_token, client_ip, hash_opaque_token, invalid_credentials, is_locked, issue_token_pair,
+    record_login_failure, record_login_success, sign_claims, validate_token, verify_claims, AuthMiddleware,
+    AuthResponse, AuthSettings,
+};
+use crate::dto::ApiResponse;
+use crate::entities::sea_orm_active_enums::UserRole;
+use crate::entities::user;
+use crate::error::AppError;
+use crate::login_throttle::LoginThrottle;
+use crate::repository::Repository;
+
+const CHALLENGE_EXPIRATION_MINUTES: i64 = 5;
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECONDS: u64 = 30;
+const RECOVERY_CODE_COUNT: usize = 10;
+/// `aud` of challenge tokens; see `auth::ACCESS_TOKEN_AUDIENCE`.
+pub(crate) const CHALLENGE_AUDIENCE: &str = "2fa-challenge";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChallengePurpose {
+    /// 2FA is enabled; the challenge is exchanged for tokens with a code.
+    Verify,
+    /// 2FA is mandatory but not set up yet; the challenge only unlocks enrollment.
+    Enroll,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ChallengeClaims {
+    sub: i32,
+    purpose: ChallengePurpose,
+    aud: String,
+    exp: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorChallenge {
+    pub challenge_token: String,
+    pub challenge_type: ChallengePurpose,
+    pub expires_in: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorLogin {
+    pub challenge_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotpCode {
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TotpSetup {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TotpEnabled {
+    pub recovery_codes: Vec<String>,
+    /// Present when enrollment was done with an enrollment challenge instead of
+    /// an access token, so the admin ends up logged in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<AuthResponse>,
+}
+
+pub fn issue_challenge(user: &user::Model, purpose: ChallengePurpose) -> Result<TwoFactorChallenge, AppError> {
+    let claims = ChallengeClaims {
+        sub: user.id,
+        purpose,
+        aud: CHALLENGE_AUDIENCE.to_string(),
+        exp: (Utc::now() + Duration::minutes(CHALLENGE_EXPIRATION_MINUTES)).timestamp(),
+    };
+    let challenge_token = sign_claims(&claims).map_err(|err| AppError::internal(format!("Token generation failed: {}", err)))?;
+
+    Ok(TwoFactorChallenge {
+        challenge_token,
+        challenge_type: purpose,
+        expires_in: CHALLENGE_EXPIRATION_MINUTES * 60,
+    })
+}
+
+fn verify_challenge(token: &str, purpose: ChallengePurpose) -> Result<i32, AppError> {
+    match verify_claims::<ChallengeClaims>(token, CHALLENGE_AUDIENCE) {
+        Ok(claims) if claims.purpose == purpose => Ok(claims.sub),
+        _ => Err(AppError::unauthorized("Invalid or expired challenge")),
+    }
+}
+
+/// Caller of the enrollment endpoints: a regular access token, or an enrollment
+/// challenge for admins who must set up 2FA before they can get one.
+pub enum TotpSubject {
+    Session(i32),
+    Enrollment(i32),
+}
+
+impl TotpSubject {
+    fn user_id(&self) -> i32 {
+        match self {
+            TotpSubject::Session(id) | TotpSubject::Enrollment(id) => *id,
+        }
+    }
+}
+
+impl FromRequest for TotpSubject {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        _payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let subject = bearer_token(req).and_then(|token| match validate_token(token) {
+            Ok(claims) => Ok(TotpSubject::Session(claims.sub)),
+            Err(_) => verify_challenge(token, ChallengePurpose::Enroll).map(TotpSubject::Enrollment),
+        });
+
+        ready(subject)
+    }
+}
+
+fn totp_for(user: &user::Model, secret: &str, settings: &AuthSettings) -> Result<TOTP, AppError> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|_| AppError::internal("Stored TOTP secret is invalid"))?;
+
+    Ok(TOTP::new_unchecked(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        1,
+        TOTP_STEP_SECONDS,
+        secret,
+        Some(settings.totp_issuer.clone()),
+        user.email.clone(),
+    ))
+}
+
+/// Checks `code` against the current time step and one step either side, then
+/// records the matching step so the same code cannot be replayed.
+async fn check_totp_code(
+    repo: &Repository,
+    user: &user::Model,
+    code: &str,
+    settings: &AuthSettings,
+) -> Result<bool, AppError> {
+    let secret = match &user.totp_secret {
+        Some(secret) => secret,
+        None => return Ok(false),
+    };
+    let totp = totp_for(user, secret, settings)?;
+
+    let current_step = Utc::now().timestamp() as u64 / TOTP_STEP_SECONDS;
+    let matching_step = (current_step.saturating_sub(1)..=current_step + 1).find(|step| {
+        let expected = totp.generate(step * TOTP_STEP_SECONDS);
+        bool::from(expected.as_bytes().ct_eq(code.trim().as_bytes()))
+    });
+
+    match matching_step {
+        Some(step) => repo
+            .record_totp_step(user.id, step as i64)
+            .await
+            .map_err(AppError::from),
+        None => Ok(false),
+    }
+}
+
+fn generate_recovery_code() -> String {
+    let mut bytes = [0u8; 8];
+    OsRng.fill_bytes(&mut bytes);
+    let code = URL_SAFE_NO_PAD.encode(bytes).to_lowercase().replace(['-', '_'], "x");
+    format!("{}-{}", &code[..5], &code[5..10])
+}
+
+// Recovery codes are compared case-insensitively and without the dash.
+fn hash_recovery_code(code: &str) -> String {
+    hash_opaque_token(&code.trim().to_lowercase().replace('-', ""))
+}
+
+async fn load_user(repo: &Repository, id: i32) -> Result<user::Model, AppError> {
+    repo.find_user_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::unauthorized("Invalid credentials"))
+}
+
+/// Second login step: trades a challenge plus a TOTP or recovery code for tokens.
+/// Wrong or missing codes count towards the same lockout as wrong passwords, and
+/// every failure gets the same answer as a wrong password.
+pub async fn verify_login(
+    req: HttpRequest,
+    body: web::Json<TwoFactorLogin>,
+    repo: web::Data<Repository>,
+    settings: web::Data<AuthSettings>,
+    throttle: web::Data<LoginThrottle>,
+) -> Result<web::Json<ApiResponse<AuthResponse>>, AppError> {
+    let ip = client_ip(&req);
+    if ip.is_some_and(|ip| throttle.is_blocked(ip)) {
+        return Err(invalid_credentials());
+    }
+
+    let user_id = verify_challenge(&body.challenge_token, ChallengePurpose::Verify).map_err(|_| invalid_credentials())?;
+    let user = load_user(&repo, user_id).await?;
+    if user.totp_enabled_at.is_none() || is_locked(&user) {
+        return Err(invalid_credentials());
+    }
+
+    let accepted = match (&body.code, &body.recovery_code) {
+        (Some(code), _) => check_totp_code(&repo, &user, code, &settings).await?,
+        (None, Some(recovery_code)) => repo.use_recovery_code(user.id, &hash_recovery_code(recovery_code)).await?,
+        (None, None) => false,
+    };
+    if !accepted {
+        record_login_failure(&repo, &throttle, Some(&user), ip).await?;
+        return Err(invalid_credentials());
+    }
+
+    record_login_success(&repo, &throttle, &user, ip).await?;
+    let response = issue_token_pair(&repo, &user).await?;
+
+    Ok(web::Json(ApiResponse::success(response, "Login successful")))
+}
+
+/// Starts enrollment: creates a new secret that only becomes active once a
+/// first code is confirmed.
+pub async fn setup(
+    subject: TotpSubject,
+    repo: web::Data<Repository>,
+    settings: web::Data<AuthSettings>,
+) -> Result<web::Json<ApiResponse<TotpSetup>>, AppError> {
+    let user = load_user(&repo, subject.user_id()).await?;
+    if user.totp_enabled_at.is_some() {
+        return Err(AppError::conflict("Two-factor authentication is already enabled"));
+    }
+
+    let secret = Secret::generate_secret().to_encoded().to_string();
+    let user = repo.set_pending_totp_secret(user.id, secret.clone()).await?;
+    let totp = totp_for(&user, &secret, &settings)?;
+
+    Ok(web::Json(ApiResponse::success(
+        TotpSetup {
+            secret,
+            otpauth_uri: totp.get_url(),
+        },
+        "Scan the secret with an authenticator app and confirm with a code",
+    )))
+}
+
+pub async fn confirm(
+    subject: TotpSubject,
+    body: web::Json<TotpCode>,
+    repo: web::Data<Repository>,
+    settings: web::Data<AuthSettings>,
+) -> Result<web::Json<ApiResponse<TotpEnabled>>, AppError> {
+    let user = load_user(&repo, subject.user_id()).await?;
+    if user.totp_enabled_at.is_some() {
+        return Err(AppError::conflict("Two-factor authentication is already enabled"));
+    }
+    if user.totp_secret.is_none() {
+        return Err(AppError::validation("Start enrollment through /auth/2fa/setup first"));
+    }
+    if !check_totp_code(&repo, &user, &body.code, &settings).await? {
+        return Err(AppError::validation("Invalid code"));
+    }
+
+    let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT).map(|_| generate_recovery_code()).collect();
+    let user = repo.enable_user_totp(user.id, recovery_codes.iter().map(|code| hash_recovery_code(code)).collect()).await?;
+
+    let tokens = match subject {
+        TotpSubject::Enrollment(_) => Some(issue_token_pair(&repo, &user).await?),
+        TotpSubject::Session(_) => None,
+    };
+
+    Ok(web::Json(ApiResponse::success(
+        TotpEnabled { recovery_codes, tokens },
+        "Two-factor authentication enabled. Store the recovery codes somewhere safe",
+    )))
+}
+
+pub async fn disable(
+    auth: AuthMiddleware,
+    body: web::Json<TotpCode>,
+    repo: web::Data<Repository>,
+    settings: web::Data<AuthSettings>,
+) -> Result<web::Json<ApiResponse<()>>, AppError> {
+    let user = load_user(&repo, auth.user_id).await?;
+    if settings.require_admin_totp && user.user_role == UserRole::Admin {
+        return Err(AppError::forbidden("Two-factor authentication is mandatory for admin accounts"));
+    }
+    if user.totp_enabled_at.is_none() {
+        return Err(AppError::validation("Two-factor authentication is not enabled"));
+    }
+    if !check_totp_code(&repo, &user, &body.code, &settings).await? {
+        return Err(AppError::validation("Invalid code"));
+    }
+
+    repo.disable_user_totp(user.id).await?;
+
+    Ok(web::Json(ApiResponse::success((), "Two-factor authentication disabled")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::generate_token;
+    use std::sync::Once;
+
+    // `jwt::keys()` is loaded once per process, so the secret must be in place first.
+    fn user() -> user::Model {
+        static KEYS: Once = Once::new();
+        KEYS.call_once(|| std::env::set_var("JWT_SECRET", "test-secret-test-secret-test-secret"));
+
+        user::Model {
+            id: 7,
+            email: "ada@example.com".to_string(),
+            first_name: "Ada".to_string(),
+            last_name: "Lovelace".to_string(),
+            user_role: UserRole::User,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            password_hash: None,
+            verified_at: None,
+            totp_secret: None,
+            totp_enabled_at: None,
+            totp_last_used_step: None,
+            failed_login_attempts: 0,
+            locked_until: None,
+        }
+    }
+
+    #[test]
+    fn challenge_is_rejected_as_a_bearer_token() {
+        let user = user();
+        for purpose in [ChallengePurpose::Verify, ChallengePurpose::Enroll] {
+            let challenge = issue_challenge(&user, purpose).unwrap().challenge_token;
+            assert_eq!(verify_challenge(&challenge, purpose).unwrap(), user.id);
+            assert!(validate_token(&challenge).is_err());
+        }
+    }
+
+    #[test]
+    fn access_token_is_rejected_as_a_challenge() {
+        let user = user();
+        let token = generate_token(&user).unwrap();
+        assert_eq!(validate_token(&token).unwrap().sub, user.id);
+        assert!(verify_challenge(&token, ChallengePurpose::Verify).is_err());
+        assert!(verify_challenge(&token, ChallengePurpose::Enroll).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+use std::ops::Deref;
+
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use chrono::{DateTime, TimeZone, Utc};
+use futures::future::LocalBoxFuture;
+use serde::{de::DeserializeOwned, Serialize};
+use validator::{Validate, ValidationError, ValidationErrors};
+
+use crate::error::AppError;
+
+/// One broken rule, as reported back to the client.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub rule: String,
+    pub message: String,
+}
+
+/// Flattens `validator`'s error map into a list sorted by field name, so the
+/// response is stable between requests.
+pub fn field_errors(errors: &ValidationErrors) -> Vec<FieldError> {
+    let mut fields: Vec<FieldError> = errors
+        .field_errors()
+        .into_iter()
+        .flat_map(|(field, errors)| {
+            errors.iter().map(move |error| FieldError {
+                field: field.to_string(),
+                rule: error.code.to_string(),
+                message: error
+                    .message
+                    .as_ref()
+                    .map(|message| message.to_string())
+                    .unwrap_or_else(|| default_message(&error.code)),
+            })
+        })
+        .collect();
+    fields.sort_by(|a, b| a.field.cmp(&b.field));
+    fields
+}
+
+fn default_message(rule: &str) -> String {
+    match rule {
+        "email" => "must be a valid email address".to_string(),
+        "url" => "must be a valid URL".to_string(),
+        "length" => "has an invalid length".to_string(),
+        "range" => "is out of range".to_string(),
+        other => format!("failed the {} rule", other),
+    }
+}
+
+pub fn validate<T: Validate>(value: &T) -> Result<(), AppError> {
+    value
+        .validate()
+        .map_err(|errors| AppError::InvalidFields(field_errors(&errors)))
+}
+
+/// `web::Json` that also runs the body's `Validate` rules; a failure becomes a
+/// 422 listing every broken field.
+pub struct ValidatedJson<T>(pub T);
+
+impl<T> ValidatedJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ValidatedJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidatedJson<T> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let json = web::Json::<T>::from_request(req, payload);
+        Box::pin(async move {
+            let value = json.await?.into_inner();
+            validate(&value)?;
+            Ok(ValidatedJson(value))
+        })
+    }
+}
+
+// Custom rules, referenced from `#[validate(custom(function = ...))]`.
+
+pub fn not_blank(value: &str) -> Result<(), ValidationError> {
+    if value.trim().is_empty() {
+        return Err(rule_error("not_blank", "must not be blank"));
+    }
+    Ok(())
+}
+
+/// International format: an optional leading `+`, then 7 to 15 digits.
+/// Spaces, dashes and dots between digits are tolerated.
+pub fn phone_number(value: &str) -> Result<(), ValidationError> {
+    let digits = value.strip_prefix('+').unwrap_or(value);
+    let well_formed = digits.starts_with(|c: char| c.is_ascii_digit())
+        && digits.ends_with(|c: char| c.is_ascii_digit())
+        && digits.chars().all(|c| c.is_ascii_digit() || matches!(c, ' ' | '-' | '.'));
+    let digit_count = digits.chars().filter(char::is_ascii_digit).count();
+
+    if !well_formed || !(7..=15).contains(&digit_count) {
+        return Err(rule_error("phone_number", "must be a phone number such as +44 20 7946 0958"));
+    }
+    Ok(())
+}
+
+/// Times such as `scheduled_at` must lie in the future.
+pub fn in_future(value: &DateTime<Utc>) -> Result<(), ValidationError> {
+    if *value <= Utc::now() {
+        return Err(rule_error("date_range", "must be in the future"));
+    }
+    Ok(())
+}
+
+/// Birth dates must lie in the past and no earlier than 1900.
+pub fn birth_date(value: &DateTime<Utc>) -> Result<(), ValidationError> {
+    let earliest = Utc.with_ymd_and_hms(1900, 1, 1, 0, 0, 0).unwrap();
+    if *value > Utc::now() {
+        return Err(rule_error("date_range", "must not be in the future"));
+    }
+    if *value < earliest {
+        return Err(rule_error("date_range", "must not be before 1900"));
+    }
+    Ok(())
+}
+
+fn rule_error(code: &'static str, message: &'static str) -> ValidationError {
+    ValidationError::new(code).with_message(message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn rule(result: Result<(), ValidationError>) -> String {
+        result.expect_err("expected the value to be rejected").code.to_string()
+    }
+
+    #[test]
+    fn not_blank_rejects_whitespace() {
+        assert!(not_blank("Ada").is_ok());
+        assert_eq!(rule(not_blank("   ")), "not_blank");
+    }
+
+    #[test]
+    fn phone_number_accepts_international_formats() {
+        assert!(phone_number("+44 20 7946 0958").is_ok());
+        assert!(phone_number("555-0100-123").is_ok());
+        assert!(phone_number("1234567").is_ok());
+        assert!(phone_number("+123456789012345").is_ok());
+    }
+
+    #[test]
+    fn phone_number_rejects_malformed_input() {
+        assert_eq!(rule(phone_number("123456")), "phone_number");
+        assert_eq!(rule(phone_number("+1234567890123456")), "phone_number");
+        assert_eq!(rule(phone_number("+44 (20) 7946 0958")), "phone_number");
+        assert_eq!(rule(phone_number("-1234567")), "phone_number");
+        assert_eq!(rule(phone_number("1234567 ")), "phone_number");
+        assert_eq!(rule(phone_number("")), "phone_number");
+    }
+
+    #[test]
+    fn birth_date_must_be_past_and_after_1900() {
+        assert!(birth_date(&Utc.with_ymd_and_hms(1990, 5, 17, 0, 0, 0).unwrap()).is_ok());
+        assert!(birth_date(&Utc.with_ymd_and_hms(1900, 1, 1, 0, 0, 0).unwrap()).is_ok());
+        assert_eq!(rule(birth_date(&(Utc::now() + Duration::days(1)))), "date_range");
+        assert_eq!(rule(birth_date(&Utc.with_ymd_and_hms(1899, 12, 31, 0, 0, 0).unwrap())), "date_range");
+    }
+
+    #[test]
+    fn in_future_rejects_past_times() {
+        assert!(in_future(&(Utc::now() + Duration::minutes(5))).is_ok());
+        assert_eq!(rule(in_future(&(Utc::now() - Duration::minutes(5)))), "date_range");
+    }
+}